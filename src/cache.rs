@@ -0,0 +1,229 @@
+use crate::metrics::CacheCounters;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/*
+ * Identifies a cached upstream response. The token is hashed so that it is not kept around in
+ * the cache keys, while still making sure that clients using different tokens don't see each
+ * other's responses. The id is the one of the requested resource, eg. a project or a pipeline.
+ */
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct CacheKey {
+    base_url: String,
+    token_hash: u64,
+    id: Option<String>,
+}
+
+impl CacheKey {
+    pub fn new(base_url: &str, auth_token: &str, id: Option<&str>) -> Self {
+        CacheKey {
+            base_url: base_url.to_string(),
+            token_hash: hash_token(auth_token),
+            id: id.map(String::from),
+        }
+    }
+}
+
+pub fn hash_token(auth_token: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    auth_token.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct Entry<V> {
+    value: V,
+    fetched_at: Instant,
+}
+
+/*
+ * In-memory cache of upstream responses.
+ *
+ * Entries younger than `ttl` are served as they are. Entries older than `ttl`, but younger than
+ * `ttl + stale_while_revalidate`, are still served, while a fresh value is fetched in the
+ * background. Anything older is fetched on the request path.
+ *
+ * A zero `ttl` disables the cache.
+ */
+pub struct ResponseCache<V> {
+    ttl: Duration,
+    stale_while_revalidate: Duration,
+    entries: Mutex<HashMap<CacheKey, Entry<V>>>,
+    refreshing: Mutex<HashSet<CacheKey>>,
+    counters: CacheCounters,
+}
+
+impl<V: Clone + 'static> ResponseCache<V> {
+    pub fn new(ttl: Duration, stale_while_revalidate: Duration, counters: CacheCounters) -> Self {
+        ResponseCache {
+            ttl,
+            stale_while_revalidate,
+            entries: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(HashSet::new()),
+            counters,
+        }
+    }
+
+    pub async fn get_or_fetch<F, Fut, E>(self: &Arc<Self>, key: CacheKey, fetch: F) -> Result<V, E>
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = Result<V, E>> + 'static,
+    {
+        if self.ttl.is_zero() {
+            return fetch().await;
+        }
+
+        match self.lookup(&key) {
+            Some((value, age)) if age < self.ttl => {
+                self.counters.hit.inc();
+                Ok(value)
+            }
+            Some((value, age)) if age < self.ttl + self.stale_while_revalidate => {
+                self.counters.stale.inc();
+                self.revalidate(key, fetch);
+                Ok(value)
+            }
+            _ => {
+                self.counters.miss.inc();
+                let value = fetch().await?;
+                self.insert(key, value.clone());
+                Ok(value)
+            }
+        }
+    }
+
+    fn lookup(&self, key: &CacheKey) -> Option<(V, Duration)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|entry| (entry.value.clone(), entry.fetched_at.elapsed()))
+    }
+
+    fn insert(&self, key: CacheKey, value: V) {
+        let max_age = self.ttl + self.stale_while_revalidate;
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.retain(|_, entry| entry.fetched_at.elapsed() < max_age);
+        entries.insert(
+            key,
+            Entry {
+                value,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+
+    fn revalidate<F, Fut, E>(self: &Arc<Self>, key: CacheKey, fetch: F)
+    where
+        F: Fn() -> Fut + 'static,
+        Fut: Future<Output = Result<V, E>> + 'static,
+    {
+        if !self.refreshing.lock().unwrap().insert(key.clone()) {
+            return;
+        }
+
+        let cache = Arc::clone(self);
+        actix_web::rt::spawn(async move {
+            if let Ok(value) = fetch().await {
+                cache.insert(key.clone(), value);
+            }
+            cache.refreshing.lock().unwrap().remove(&key);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn counting_fetch(calls: &Rc<Cell<u32>>) -> impl Fn() -> std::future::Ready<Result<u32, ()>> {
+        let calls = Rc::clone(calls);
+        move || {
+            calls.set(calls.get() + 1);
+            std::future::ready(Ok(calls.get()))
+        }
+    }
+
+    #[actix_web::test]
+    async fn serves_fresh_entries_from_the_cache() {
+        let cache = Arc::new(ResponseCache::new(
+            Duration::from_secs(60),
+            Duration::ZERO,
+            CacheCounters::default(),
+        ));
+        let calls = Rc::new(Cell::new(0));
+        let key = CacheKey::new("http://ci", "token", Some("project"));
+
+        let first = cache.get_or_fetch(key.clone(), counting_fetch(&calls)).await;
+        let second = cache.get_or_fetch(key, counting_fetch(&calls)).await;
+
+        assert_eq!(first, Ok(1));
+        assert_eq!(second, Ok(1));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(cache.counters.miss.get(), 1);
+        assert_eq!(cache.counters.hit.get(), 1);
+    }
+
+    #[actix_web::test]
+    async fn does_not_share_entries_between_tokens() {
+        let cache = Arc::new(ResponseCache::new(
+            Duration::from_secs(60),
+            Duration::ZERO,
+            CacheCounters::default(),
+        ));
+        let calls = Rc::new(Cell::new(0));
+
+        let first = cache
+            .get_or_fetch(CacheKey::new("http://ci", "token", None), counting_fetch(&calls))
+            .await;
+        let second = cache
+            .get_or_fetch(CacheKey::new("http://ci", "other-token", None), counting_fetch(&calls))
+            .await;
+
+        assert_eq!(first, Ok(1));
+        assert_eq!(second, Ok(2));
+    }
+
+    #[actix_web::test]
+    async fn serves_stale_entries_while_revalidating() {
+        let cache = Arc::new(ResponseCache::new(
+            Duration::from_millis(50),
+            Duration::from_secs(60),
+            CacheCounters::default(),
+        ));
+        let calls = Rc::new(Cell::new(0));
+        let key = CacheKey::new("http://ci", "token", None);
+
+        cache.get_or_fetch(key.clone(), counting_fetch(&calls)).await.unwrap();
+        actix_web::rt::time::sleep(Duration::from_millis(60)).await;
+
+        let stale = cache.get_or_fetch(key.clone(), counting_fetch(&calls)).await;
+        actix_web::rt::task::yield_now().await;
+        let revalidated = cache.get_or_fetch(key, counting_fetch(&calls)).await;
+
+        assert_eq!(stale, Ok(1));
+        assert_eq!(revalidated, Ok(2));
+    }
+
+    #[actix_web::test]
+    async fn bypasses_the_cache_when_ttl_is_zero() {
+        let cache = Arc::new(ResponseCache::new(
+            Duration::ZERO,
+            Duration::ZERO,
+            CacheCounters::default(),
+        ));
+        let calls = Rc::new(Cell::new(0));
+        let key = CacheKey::new("http://ci", "token", None);
+
+        cache.get_or_fetch(key.clone(), counting_fetch(&calls)).await.unwrap();
+        cache.get_or_fetch(key, counting_fetch(&calls)).await.unwrap();
+
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -1,13 +1,21 @@
+use crate::config::{
+    ErrorStatus, FeedConfig, LabelPlaceholder, LabelTemplate, NamePattern, NamePlaceholder, NameTemplate,
+};
 use crate::semaphoreci;
-use crate::semaphoreci::{Pipeline, State};
+use crate::semaphoreci::{JobStatus, Pipeline, PipelineDetails, ResultReason, State, Workflow};
 use chrono::DateTime;
 use itertools::Itertools;
+use quick_xml::events::{BytesDecl, Event};
+use quick_xml::Writer;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub enum Activity {
+    #[default]
     Sleeping,
     Building,
-    #[allow(dead_code)]
     CheckingModifications,
 }
 
@@ -21,16 +29,32 @@ impl Activity {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
 pub enum BuildStatus {
     Success,
     Failure,
-    #[allow(dead_code)]
     Exception,
+    #[default]
     Unknown,
 }
 
+impl From<ErrorStatus> for BuildStatus {
+    fn from(status: ErrorStatus) -> Self {
+        match status {
+            ErrorStatus::Exception => BuildStatus::Exception,
+            ErrorStatus::Failure => BuildStatus::Failure,
+        }
+    }
+}
+
 impl BuildStatus {
+    pub const ALL: [BuildStatus; 4] = [
+        BuildStatus::Success,
+        BuildStatus::Failure,
+        BuildStatus::Exception,
+        BuildStatus::Unknown,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             BuildStatus::Success => "Success",
@@ -41,7 +65,16 @@ impl BuildStatus {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/*
+ * A cctray project. Fields which are not part of the cctray format, such as the SemaphoreCI ids of
+ * the latest pipeline, are only included in the JSON output. Optional cctray fields are left out
+ * when empty.
+ *
+ * SemaphoreCI doesn't tell when the next pipeline of a project will run, so `next_build_time` is
+ * never set from pipelines.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CCTrayProjectInfo {
     pub name: String,
     pub activity: Activity,
@@ -49,6 +82,116 @@ pub struct CCTrayProjectInfo {
     pub last_build_label: String,
     pub last_build_time: String,
     pub web_url: String,
+    pub workflow_id: String,
+    pub pipeline_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_build_time: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<Message>,
+    /// The commit of the last completed pipeline, when it broke the build.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breaking_commit: Option<Commit>,
+    /// The last completed pipeline, which the messages are about.
+    #[serde(skip)]
+    pub last_build_pipeline_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum MessageKind {
+    Breakers,
+    FailingTasks,
+}
+
+impl MessageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageKind::Breakers => "Breakers",
+            MessageKind::FailingTasks => "FailingTasks",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Message {
+    pub kind: MessageKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Commit {
+    pub sha: String,
+    /// Not part of the documented pipeline API, so it is always serialized, as `null` when
+    /// SemaphoreCI doesn't report it, for clients to handle the missing case.
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl CCTrayProjectInfo {
+    /// Whether messages explaining the last build status are worth fetching.
+    pub fn is_broken(&self) -> bool {
+        matches!(self.last_build_status, BuildStatus::Failure | BuildStatus::Exception)
+    }
+
+    /// A project of a single pipeline, for tests which only care about its name and status.
+    #[cfg(test)]
+    pub(crate) fn with_status(name: &str, activity: Activity, last_build_status: BuildStatus) -> Self {
+        CCTrayProjectInfo {
+            name: name.to_string(),
+            activity,
+            last_build_status,
+            last_build_label: String::from("label"),
+            last_build_time: String::from("2025-03-24T14:35:23+00:00"),
+            web_url: String::from("https://org.semaphoreci.com/workflows/1"),
+            workflow_id: String::from("1"),
+            pipeline_id: String::from("2"),
+            last_build_pipeline_id: Some(String::from("2")),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct CCTrayProjects<'a> {
+    projects: &'a [CCTrayProjectInfo],
+}
+
+/*
+ * Options affecting how pipelines are turned into cctray projects. Projects keep their default
+ * name, eg. the pipeline name, unless there is a name template, which may refer to the SemaphoreCI
+ * project the pipelines belong to. Likewise, builds are labelled with their pipeline id unless
+ * there is a label template. Promotions are listed whether or not they were triggered from the
+ * latest build, unless `hide_stale_promotions` is set.
+ */
+#[derive(Debug, Clone)]
+pub struct FeedOptions {
+    pub stopped_status: BuildStatus,
+    pub errored_status: BuildStatus,
+    pub name_template: Option<NameTemplate>,
+    pub label_template: Option<LabelTemplate>,
+    pub project: String,
+    pub hide_stale_promotions: bool,
+}
+
+impl Default for FeedOptions {
+    fn default() -> Self {
+        FeedOptions::from(&FeedConfig::default())
+    }
+}
+
+impl From<&FeedConfig> for FeedOptions {
+    fn from(config: &FeedConfig) -> Self {
+        FeedOptions {
+            stopped_status: config.stopped_status.into(),
+            errored_status: config.errored_status.into(),
+            name_template: config.name_template.clone(),
+            label_template: config.label.clone(),
+            project: String::new(),
+            hide_stale_promotions: config.hide_stale_promotions,
+        }
+    }
 }
 
 /*
@@ -65,14 +208,16 @@ pub struct CCTrayProjectInfo {
  * Excluding these pipelines is not ideal. It is useful to see when a build is queued or when it
  * failed before starting. The problem is that at the moment we cannot represent these build in a
  * meaningful way, mainly because we are using the pipeline name for grouping pipelines into cctray
- * projects. Grouping by workflow id would maybe work better.
+ * projects. Grouping by workflow (see `to_cctray_project_info_by_workflow`) doesn't have this
+ * problem, and includes them.
  */
-const TEMPORARY_PIPELINE_NAME: &'static str = "Pipeline";
+const TEMPORARY_PIPELINE_NAME: &str = "Pipeline";
 
 fn get_cctray_project_info(
-    name: &String,
-    pipelines: &Vec<&Pipeline>,
-    org: &String,
+    name: &str,
+    pipelines: &[&Pipeline],
+    org: &str,
+    options: &FeedOptions,
 ) -> CCTrayProjectInfo {
     let sorted_pipelines: Vec<&&Pipeline> = pipelines
         .iter()
@@ -80,25 +225,26 @@ fn get_cctray_project_info(
         .rev()
         .collect();
 
-    let latest_pipeline = sorted_pipelines.get(0).unwrap();
-    let last_completed_pipeline = sorted_pipelines.iter().find(|p| p.state == State::DONE);
+    let latest_pipeline = sorted_pipelines.first().unwrap();
+    /* Pipelines stopped by a user or superseded by a newer one don't tell whether the build is
+     * broken, so the project reports the completed pipeline before them, if there is one */
+    let completed_pipelines = sorted_pipelines.iter().filter(|p| p.state == State::DONE);
+    let last_completed_pipeline = completed_pipelines
+        .clone()
+        .find(|p| !p.is_superseded())
+        .or_else(|| completed_pipelines.clone().next());
 
     let activity = match latest_pipeline.state {
-        State::RUNNING => Activity::Building,
+        State::INITIALIZING | State::PENDING | State::QUEUING => Activity::CheckingModifications,
+        State::RUNNING | State::STOPPING => Activity::Building,
         State::DONE => Activity::Sleeping,
         _ => Activity::Sleeping,
     };
 
-    let last_pipeline_result = last_completed_pipeline
-        .and_then(|p| p.result.clone());
-
-    let last_build_status = match last_pipeline_result{
-        Some(semaphoreci::Result::PASSED) => BuildStatus::Success,
-        Some(semaphoreci::Result::FAILED) => BuildStatus::Failure,
-        _ => BuildStatus::Unknown
-    };
+    let last_build_status = last_completed_pipeline
+        .map_or(BuildStatus::Unknown, |p| get_build_status(p, options));
 
-    let last_build_label = last_completed_pipeline.map_or_else(|| "", |p| &p.ppl_id);
+    let last_build_label = last_completed_pipeline.map_or_else(String::new, |p| get_build_label(p, options));
     let last_build_time = last_completed_pipeline
         .and_then(|p| DateTime::from_timestamp(p.done_at.seconds, 0))
         .map_or_else(|| String::from(""), |dt| dt.to_rfc3339());
@@ -107,51 +253,540 @@ fn get_cctray_project_info(
         org, latest_pipeline.wf_id, latest_pipeline.ppl_id
     );
 
+    let name = match &options.name_template {
+        Some(template) => template.render(|placeholder| match placeholder {
+            NamePlaceholder::Org => org.to_string(),
+            NamePlaceholder::Project => options.project.clone(),
+            NamePlaceholder::Pipeline => latest_pipeline.name.clone(),
+            NamePlaceholder::Branch => latest_pipeline.branch_name.clone().unwrap_or_default(),
+            NamePlaceholder::Name => name.to_string(),
+        }),
+        None => name.to_string(),
+    };
+
     CCTrayProjectInfo {
-        name: name.clone(),
+        name,
         activity,
         last_build_status,
-        last_build_label: last_build_label.to_string(),
+        last_build_label,
         last_build_time,
         web_url,
+        workflow_id: latest_pipeline.wf_id.clone(),
+        pipeline_id: latest_pipeline.ppl_id.clone(),
+        category: options.project.clone(),
+        next_build_time: None,
+        messages: Vec::new(),
+        breaking_commit: None,
+        last_build_pipeline_id: last_completed_pipeline.map(|p| p.ppl_id.clone()),
     }
 }
 
-pub fn to_cctray_project_info(pipelines: Vec<Pipeline>, org: &String) -> Vec<CCTrayProjectInfo> {
+/// Renders the label template, falling back to the pipeline id when it refers to missing values.
+fn get_build_label(pipeline: &Pipeline, options: &FeedOptions) -> String {
+    options
+        .label_template
+        .as_ref()
+        .and_then(|template| {
+            template.try_render(|placeholder| match placeholder {
+                LabelPlaceholder::Sha => pipeline
+                    .commit_sha
+                    .as_ref()
+                    .filter(|sha| !sha.is_empty())
+                    .map(|sha| sha.chars().take(7).collect()),
+                LabelPlaceholder::Branch => pipeline.branch_name.clone().filter(|_| pipeline.tag_name().is_none()),
+                LabelPlaceholder::Tag => pipeline.tag_name().map(String::from),
+                LabelPlaceholder::WorkflowNumber => pipeline.workflow_number.map(|n| n.to_string()),
+                LabelPlaceholder::Id => Some(pipeline.ppl_id.clone()),
+            })
+        })
+        .unwrap_or_else(|| pipeline.ppl_id.clone())
+}
+
+fn get_build_status(pipeline: &Pipeline, options: &FeedOptions) -> BuildStatus {
+    get_result_status(pipeline.result.as_ref(), pipeline.result_reason.as_ref(), options)
+}
+
+fn get_result_status(
+    result: Option<&semaphoreci::Result>,
+    result_reason: Option<&ResultReason>,
+    options: &FeedOptions,
+) -> BuildStatus {
+    match (result, result_reason) {
+        /* SemaphoreCI reports internal errors as stopped or canceled pipelines, not failed ones */
+        (_, Some(reason)) if reason.is_error() => options.errored_status,
+        (Some(semaphoreci::Result::PASSED), _) => BuildStatus::Success,
+        (Some(semaphoreci::Result::FAILED), _) => BuildStatus::Failure,
+        (Some(semaphoreci::Result::STOPPED | semaphoreci::Result::CANCELED), _) => {
+            options.stopped_status
+        }
+        _ => BuildStatus::Unknown,
+    }
+}
+
+/*
+ * Names the pipelines triggered by promotions after the pipelines they were promoted from, eg.
+ * "build → deploy-staging", so that each promotion target is a cctray project of its own, even when
+ * several pipelines promote to the same one. Promotions of pipelines which aren't listed keep their
+ * own name, as do pipelines which SemaphoreCI still names "Pipeline".
+ *
+ * With `hide_stale`, promotions are left out unless they were triggered from the latest run
+ * of their parent pipeline on the same branch, eg. a deployment of a previous build is hidden, so
+ * its project disappears from the feed whenever a new build runs, until that build is promoted.
+ */
+fn name_promotions(pipelines: Vec<Pipeline>, hide_stale: bool) -> Vec<Pipeline> {
+    let (names, keep): (Vec<String>, Vec<bool>) = {
+        let pipelines_by_id: HashMap<&str, &Pipeline> = pipelines.iter().map(|p| (p.ppl_id.as_str(), p)).collect();
+        let key = |p: &Pipeline| (get_promotion_name(p, &pipelines_by_id), p.branch_name.clone());
+
+        let latest_runs = pipelines
+            .iter()
+            .map(|p| (key(p), p))
+            .into_grouping_map()
+            .max_by_key(|_, p| p.created_at.seconds);
+        let parent = |p: &Pipeline| p.promoted_from().and_then(|id| pipelines_by_id.get(id).copied());
+        /* Promotions of hidden promotions are hidden too, eg. a production deployment of a staging
+         * deployment of a previous build */
+        let is_current = |p: &Pipeline| {
+            std::iter::successors(parent(p), |p| parent(p))
+                .take(pipelines.len())
+                .all(|ancestor| latest_runs[&key(ancestor)].ppl_id == ancestor.ppl_id)
+        };
+        let triggered: HashSet<_> = pipelines
+            .iter()
+            .filter(|p| parent(p).is_some() && is_current(p))
+            .map(key)
+            .collect();
+
+        pipelines
+            .iter()
+            .map(|p| {
+                let (name, branch) = key(p);
+                let keep = !hide_stale || parent(p).is_none() || triggered.contains(&(name.clone(), branch));
+                (name, keep)
+            })
+            .unzip()
+    };
+
+    pipelines
+        .into_iter()
+        .zip(names)
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|((pipeline, name), _)| Pipeline { name, ..pipeline })
+        .collect()
+}
+
+/// The name of a pipeline, preceded by the names of the pipelines it was promoted from.
+fn get_promotion_name(pipeline: &Pipeline, pipelines_by_id: &HashMap<&str, &Pipeline>) -> String {
+    if pipeline.name == TEMPORARY_PIPELINE_NAME {
+        return pipeline.name.clone();
+    }
+
+    /* Bounded by the number of pipelines, in case promotions were ever reported in a loop */
+    let names = std::iter::successors(Some(pipeline), |p| p.promoted_from().and_then(|id| pipelines_by_id.get(id).copied()))
+        .take(pipelines_by_id.len())
+        .map(|p| p.name.as_str())
+        .collect_vec();
+
+    names.iter().rev().join(" → ")
+}
+
+pub fn to_cctray_project_info(
+    pipelines: Vec<Pipeline>,
+    org: &str,
+    options: &FeedOptions,
+) -> Vec<CCTrayProjectInfo> {
+    let pipelines = name_promotions(pipelines, options.hide_stale_promotions);
     let pipelines_by_name = pipelines.iter().into_group_map_by(|p| p.name.clone());
 
-    Vec::from_iter(
+    sort_by_last_build_time(
         pipelines_by_name
             .iter()
-            .filter(|(name, _pipelines)| **name != String::from(TEMPORARY_PIPELINE_NAME))
-            .map(|(name, pipelines)| get_cctray_project_info(name, pipelines, org))
+            .filter(|(name, _pipelines)| *name != TEMPORARY_PIPELINE_NAME)
+            .map(|(name, pipelines)| get_cctray_project_info(name, pipelines, org, options)),
+    )
+}
+
+/*
+ * Groups pipelines into one cctray project per pipeline name and branch, so that a failure on a
+ * feature branch doesn't hide the status of the main branch. Projects are named after both, eg.
+ * "build [main]".
+ */
+pub fn to_cctray_project_info_by_pipeline_and_branch(
+    pipelines: Vec<Pipeline>,
+    org: &str,
+    options: &FeedOptions,
+) -> Vec<CCTrayProjectInfo> {
+    let pipelines = name_promotions(pipelines, options.hide_stale_promotions);
+    let pipelines_by_name_and_branch = pipelines
+        .iter()
+        .filter(|p| p.name != TEMPORARY_PIPELINE_NAME)
+        .into_group_map_by(|p| match &p.branch_name {
+            Some(branch) => format!("{} [{}]", p.name, branch),
+            None => p.name.clone(),
+        });
+
+    sort_by_last_build_time(
+        pipelines_by_name_and_branch
+            .iter()
+            .map(|(name, pipelines)| get_cctray_project_info(name, pipelines, org, options)),
+    )
+}
+
+/*
+ * Groups pipelines into one cctray project per branch, using the workflows the pipelines belong
+ * to. Unlike grouping by pipeline name, this also attributes pipelines which SemaphoreCI still
+ * names "Pipeline" (eg. queued builds, or builds which failed before starting) to a project.
+ *
+ * Pipelines whose workflow is not listed are skipped, as they cannot be attributed to a branch.
+ */
+pub fn to_cctray_project_info_by_workflow(
+    pipelines: Vec<Pipeline>,
+    workflows: Vec<Workflow>,
+    org: &str,
+    options: &FeedOptions,
+) -> Vec<CCTrayProjectInfo> {
+    let branches_by_workflow: HashMap<String, String> = workflows
+        .into_iter()
+        .map(|w| (w.wf_id, w.branch_name))
+        .collect();
+
+    let pipelines_by_branch = pipelines
+        .iter()
+        .filter_map(|p| branches_by_workflow.get(&p.wf_id).map(|branch| (branch.clone(), p)))
+        .into_group_map();
+
+    sort_by_last_build_time(
+        pipelines_by_branch
+            .iter()
+            .map(|(branch, pipelines)| get_cctray_project_info(branch, pipelines, org, options)),
+    )
+}
+
+/*
+ * Builds a single feed out of the feeds of several SemaphoreCI projects. Project names are
+ * prefixed with the SemaphoreCI project name, because pipeline names such as "build" are usually
+ * shared by every project of an organisation. Feeds without a project name, eg. because their
+ * projects are named with a template, are kept as they are.
+ */
+pub fn merge_project_feeds(
+    cctray_projects_by_project: Vec<(Option<String>, Vec<CCTrayProjectInfo>)>,
+) -> Vec<CCTrayProjectInfo> {
+    sort_by_last_build_time(cctray_projects_by_project.into_iter().flat_map(
+        |(project_name, cctray_projects)| {
+            cctray_projects
+                .into_iter()
+                .map(move |info| match &project_name {
+                    Some(project_name) => CCTrayProjectInfo {
+                        name: format!("{}/{}", project_name, info.name),
+                        ..info
+                    },
+                    None => info,
+                })
+        },
+    ))
+}
+
+/// Keeps the projects matching any of the `include` patterns, or all of them when there are none,
+/// then drops the ones matching any of the `exclude` patterns.
+pub fn filter_by_name(
+    cctray_projects: Vec<CCTrayProjectInfo>,
+    include: &[NamePattern],
+    exclude: &[NamePattern],
+) -> Vec<CCTrayProjectInfo> {
+    cctray_projects
+        .into_iter()
+        .filter(|p| include.is_empty() || include.iter().any(|pattern| pattern.is_match(&p.name)))
+        .filter(|p| !exclude.iter().any(|pattern| pattern.is_match(&p.name)))
+        .collect()
+}
+
+/*
+ * Tells who broke the build and which blocks failed, from the details of the last completed
+ * pipeline of a project. Nothing is said about projects whose build isn't broken.
+ */
+pub fn add_messages(cctray_project: &mut CCTrayProjectInfo, details: &PipelineDetails) {
+    if !cctray_project.is_broken() {
+        return;
+    }
+
+    let pipeline = &details.pipeline;
+    let author = pipeline.commit_author.clone().filter(|a| !a.is_empty());
+
+    cctray_project.breaking_commit = pipeline
+        .commit_sha
+        .clone()
+        .filter(|sha| !sha.is_empty())
+        .map(|sha| Commit {
+            sha,
+            author: author.clone(),
+            message: pipeline.commit_message.clone().filter(|m| !m.is_empty()),
+        });
+
+    if let Some(author) = author {
+        cctray_project.messages.push(Message {
+            kind: MessageKind::Breakers,
+            text: author,
+        });
+    }
+
+    let failing_blocks = details
+        .blocks
+        .iter()
+        .filter(|block| block.result.as_ref().is_some_and(|r| *r != semaphoreci::Result::PASSED))
+        .map(|block| block.name.as_str())
+        .join(", ");
+    if !failing_blocks.is_empty() {
+        cctray_project.messages.push(Message {
+            kind: MessageKind::FailingTasks,
+            text: failing_blocks,
+        });
+    }
+}
+
+/// A block, or a job of a block, of a pipeline, as a cctray project of its own.
+struct Task<'a> {
+    name: String,
+    activity: Activity,
+    result: Option<&'a semaphoreci::Result>,
+    result_reason: Option<&'a ResultReason>,
+}
+
+fn get_tasks<'a>(details: &'a PipelineDetails, jobs: bool, pipeline_activity: &Activity) -> Vec<Task<'a>> {
+    /* Blocks waiting for others, and jobs in an unknown status, are as busy as their pipeline */
+    let default_activity = || match pipeline_activity {
+        Activity::Sleeping => Activity::Sleeping,
+        _ => Activity::CheckingModifications,
+    };
+
+    details
+        .blocks
+        .iter()
+        .flat_map(|block| {
+            if !jobs {
+                let activity = match block.state {
+                    Some(State::RUNNING | State::STOPPING) => Activity::Building,
+                    Some(State::DONE) => Activity::Sleeping,
+                    _ => default_activity(),
+                };
+
+                return vec![Task {
+                    name: block.name.clone(),
+                    activity,
+                    result: block.result.as_ref(),
+                    result_reason: block.result_reason.as_ref(),
+                }];
+            }
+
+            block
+                .jobs
+                .iter()
+                .map(|job| Task {
+                    name: format!("{} :: {}", block.name, job.name),
+                    activity: match job.status {
+                        Some(JobStatus::PENDING | JobStatus::QUEUED) => Activity::CheckingModifications,
+                        Some(JobStatus::RUNNING) => Activity::Building,
+                        Some(JobStatus::FINISHED) => Activity::Sleeping,
+                        _ => default_activity(),
+                    },
+                    result: job.result.as_ref(),
+                    result_reason: None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/*
+ * Splits cctray projects into one project per block of their pipelines, named eg.
+ * "build :: Unit tests", or per job when `jobs` is set, eg. "build :: Unit tests :: rspec".
+ * Activities come from the latest pipeline and build statuses from the last completed one, like
+ * for whole pipelines. Projects whose pipeline details are unknown, or without any blocks or jobs,
+ * are kept as they are.
+ *
+ * Messages are left out, as they are added once the projects are split, to the broken ones only.
+ */
+pub fn split_by_task(
+    cctray_projects: Vec<CCTrayProjectInfo>,
+    details: &HashMap<String, PipelineDetails>,
+    jobs: bool,
+    options: &FeedOptions,
+) -> Vec<CCTrayProjectInfo> {
+    cctray_projects
+        .into_iter()
+        .flat_map(|project| {
+            let latest_tasks = details
+                .get(&project.pipeline_id)
+                .map_or_else(Vec::new, |d| get_tasks(d, jobs, &project.activity));
+            let last_build_tasks = project
+                .last_build_pipeline_id
+                .as_ref()
+                .and_then(|id| details.get(id))
+                .map_or_else(Vec::new, |d| get_tasks(d, jobs, &Activity::Sleeping));
+
+            let names: Vec<&str> = latest_tasks
+                .iter()
+                .chain(&last_build_tasks)
+                .map(|task| task.name.as_str())
+                .unique()
+                .collect();
+            if names.is_empty() {
+                return vec![project];
+            }
+
+            names
+                .into_iter()
+                .map(|name| {
+                    let activity = latest_tasks
+                        .iter()
+                        .find(|t| t.name == name)
+                        .map_or(Activity::Sleeping, |t| t.activity.clone());
+                    let last_build_status = last_build_tasks
+                        .iter()
+                        .find(|t| t.name == name)
+                        .map_or(BuildStatus::Unknown, |t| get_result_status(t.result, t.result_reason, options));
+
+                    CCTrayProjectInfo {
+                        name: format!("{} :: {}", project.name, name),
+                        activity,
+                        last_build_status,
+                        messages: Vec::new(),
+                        breaking_commit: None,
+                        ..project.clone()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn sort_by_last_build_time(
+    cctray_projects: impl Iterator<Item = CCTrayProjectInfo>,
+) -> Vec<CCTrayProjectInfo> {
+    Vec::from_iter(
+        cctray_projects
             .sorted_by_key(|i| i.last_build_time.clone())
             .rev(),
     )
 }
 
-fn serialize_project(info: &CCTrayProjectInfo) -> String {
-    format!(
-        "<Project name=\"{}\" activity=\"{}\" lastBuildStatus=\"{}\" lastBuildLabel=\"{}\" lastBuildTime=\"{}\" webUrl=\"{}\"/>",
-        info.name, info.activity.as_str(), info.last_build_status.as_str(), info.last_build_label, info.last_build_time, info.web_url
-    )
+fn serialize_project(writer: &mut Writer<Vec<u8>>, info: &CCTrayProjectInfo) -> io::Result<()> {
+    let optional_attributes = [
+        ("nextBuildTime", info.next_build_time.as_deref().unwrap_or_default()),
+        ("category", info.category.as_str()),
+    ];
+
+    let element = writer.create_element("Project").with_attributes(
+        [
+            ("name", info.name.as_str()),
+            ("activity", info.activity.as_str()),
+            ("lastBuildStatus", info.last_build_status.as_str()),
+            ("lastBuildLabel", info.last_build_label.as_str()),
+            ("lastBuildTime", info.last_build_time.as_str()),
+            ("webUrl", info.web_url.as_str()),
+        ]
+        .into_iter()
+        .chain(optional_attributes.into_iter().filter(|(_, value)| !value.is_empty())),
+    );
+
+    if info.messages.is_empty() {
+        element.write_empty()?;
+    } else {
+        element.write_inner_content(|writer| {
+            writer.create_element("messages").write_inner_content(|writer| {
+                info.messages.iter().try_for_each(|message| {
+                    writer
+                        .create_element("message")
+                        .with_attributes([("kind", message.kind.as_str()), ("text", message.text.as_str())])
+                        .write_empty()
+                        .map(|_| ())
+                })
+            })?;
+            Ok(())
+        })?;
+    }
+
+    Ok(())
 }
 
 pub fn serialize(cctray_projects: Vec<CCTrayProjectInfo>) -> String {
-    let xml_fragment = cctray_projects
-        .iter()
-        .map(serialize_project)
-        .join("\n");
+    let mut writer = Writer::new(Vec::new());
+
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .and_then(|_| {
+            writer
+                .create_element("Projects")
+                .write_inner_content(|writer| {
+                    cctray_projects
+                        .iter()
+                        .try_for_each(|info| serialize_project(writer, info))
+                })
+                .map(|_| ())
+        })
+        .expect("Writing XML to memory cannot fail");
 
-    format!("<Projects>{}</Projects>", xml_fragment)
+    String::from_utf8(writer.into_inner()).expect("XML writer only outputs UTF-8")
+}
+
+pub fn serialize_json(cctray_projects: Vec<CCTrayProjectInfo>) -> String {
+    serde_json::to_string(&CCTrayProjects {
+        projects: &cctray_projects,
+    })
+    .expect("cctray projects can always be serialised to JSON")
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cctray::{Activity, BuildStatus, CCTrayProjectInfo};
-    use crate::semaphoreci::Result::{FAILED, PASSED};
-    use crate::semaphoreci::{Pipeline, State, Timestamp};
-    use crate::cctray::to_cctray_project_info;
+    use super::*;
+    use crate::config::NamePattern;
+    use crate::semaphoreci::Result::{CANCELED, FAILED, PASSED, STOPPED};
+    use crate::semaphoreci::Timestamp;
+
+    /// A passed pipeline, done 100 seconds after it was created.
+    fn pipeline(name: &str, ppl_id: &str, wf_id: &str, created_at: i64) -> Pipeline {
+        Pipeline {
+            name: name.to_string(),
+            state: State::DONE,
+            result: Some(PASSED),
+            result_reason: None,
+            ppl_id: ppl_id.to_string(),
+            wf_id: wf_id.to_string(),
+            branch_name: None,
+            commit_sha: None,
+            workflow_number: None,
+            promotion_of: None,
+            created_at: Timestamp { seconds: created_at },
+            done_at: Timestamp { seconds: created_at + 100 },
+        }
+    }
+
+    fn completed_pipeline(
+        result: crate::semaphoreci::Result,
+        result_reason: Option<ResultReason>,
+    ) -> Pipeline {
+        Pipeline {
+            result: Some(result),
+            result_reason,
+            ..pipeline("foo", "ppl1", "wf1", 1000)
+        }
+    }
+
+    /// The sleeping, successful project of a single pipeline of `org-name`.
+    fn cctray_project(name: &str, ppl_id: &str, wf_id: &str, last_build_time: &str) -> CCTrayProjectInfo {
+        CCTrayProjectInfo {
+            name: name.to_string(),
+            activity: Activity::Sleeping,
+            last_build_status: BuildStatus::Success,
+            last_build_label: ppl_id.to_string(),
+            last_build_time: last_build_time.to_string(),
+            web_url: format!("https://org-name.semaphoreci.com/workflows/{}?pipeline_id={}", wf_id, ppl_id),
+            workflow_id: wf_id.to_string(),
+            pipeline_id: ppl_id.to_string(),
+            last_build_pipeline_id: Some(ppl_id.to_string()),
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn convert_sem_pipelines_to_cctray_projects() {
@@ -163,12 +798,13 @@ mod tests {
             wf_id: String::from("wf1"),
             created_at: Timestamp { seconds: 1000 },
             done_at: Timestamp { seconds: 1100 },
+            ..Default::default()
         };
 
         let sem_pipelines = vec![pipeline1];
 
         let org = String::from("org-name");
-        let cctray_projects = to_cctray_project_info(sem_pipelines, &org);
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
 
         assert_eq!(
             cctray_projects,
@@ -181,6 +817,10 @@ mod tests {
                 web_url: String::from(
                     "https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1"
                 ),
+                workflow_id: String::from("wf1"),
+                pipeline_id: String::from("ppl1"),
+                last_build_pipeline_id: Some(String::from("ppl1")),
+                ..Default::default()
             }]
         );
     }
@@ -196,6 +836,7 @@ mod tests {
                 wf_id: String::from("wf2"),
                 created_at: Timestamp { seconds: 3000 },
                 done_at: Timestamp { seconds: 3100 },
+                ..Default::default()
             },
             Pipeline {
                 name: String::from("foo"),
@@ -205,6 +846,7 @@ mod tests {
                 wf_id: String::from("wf1"),
                 created_at: Timestamp { seconds: 2000 },
                 done_at: Timestamp { seconds: 2100 },
+                ..Default::default()
             },
             Pipeline {
                 name: String::from("bar"),
@@ -214,11 +856,12 @@ mod tests {
                 wf_id: String::from("wf1"),
                 created_at: Timestamp { seconds: 1000 },
                 done_at: Timestamp { seconds: 1100 },
+                ..Default::default()
             },
         ];
 
         let org = String::from("org-name");
-        let cctray_projects = to_cctray_project_info(sem_pipelines, &org);
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
 
         assert_eq!(
             cctray_projects,
@@ -232,6 +875,10 @@ mod tests {
                     web_url: String::from(
                         "https://org-name.semaphoreci.com/workflows/wf2?pipeline_id=ppl3"
                     ),
+                    workflow_id: String::from("wf2"),
+                    pipeline_id: String::from("ppl3"),
+                    last_build_pipeline_id: Some(String::from("ppl2")),
+                    ..Default::default()
                 },
                 CCTrayProjectInfo {
                     name: String::from("bar"),
@@ -242,6 +889,10 @@ mod tests {
                     web_url: String::from(
                         "https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1"
                     ),
+                    workflow_id: String::from("wf1"),
+                    pipeline_id: String::from("ppl1"),
+                    last_build_pipeline_id: Some(String::from("ppl1")),
+                    ..Default::default()
                 }
             ]
         );
@@ -258,6 +909,7 @@ mod tests {
                 wf_id: String::from("wf2"),
                 created_at: Timestamp { seconds: 2000 },
                 done_at: Timestamp { seconds: 2100 },
+                ..Default::default()
             },
             Pipeline {
                 name: String::from("Pipeline"),
@@ -267,11 +919,12 @@ mod tests {
                 wf_id: String::from("wf1"),
                 created_at: Timestamp { seconds: 1000 },
                 done_at: Timestamp { seconds: 1100 },
+                ..Default::default()
             },
         ];
 
         let org = String::from("org-name");
-        let cctray_projects = to_cctray_project_info(sem_pipelines, &org);
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
 
         assert_eq!(
             cctray_projects,
@@ -285,8 +938,693 @@ mod tests {
                     web_url: String::from(
                         "https://org-name.semaphoreci.com/workflows/wf2?pipeline_id=ppl2"
                     ),
+                    workflow_id: String::from("wf2"),
+                    pipeline_id: String::from("ppl2"),
+                    last_build_pipeline_id: Some(String::from("ppl2")),
+                    ..Default::default()
                 }
             ]
         );
     }
+
+    #[test]
+    fn prefixes_project_names_when_merging_projects_of_an_organisation() {
+        let api_pipelines = vec![pipeline("build", "ppl1", "wf1", 1000)];
+        let web_pipelines = vec![Pipeline {
+            result: Some(FAILED),
+            ..pipeline("build", "ppl2", "wf2", 2000)
+        }];
+
+        let org = String::from("org-name");
+        let cctray_projects = merge_project_feeds(vec![
+            (Some(String::from("api")), to_cctray_project_info(api_pipelines, &org, &FeedOptions::default())),
+            (Some(String::from("web")), to_cctray_project_info(web_pipelines, &org, &FeedOptions::default())),
+        ]);
+
+        assert_eq!(
+            cctray_projects,
+            vec![
+                CCTrayProjectInfo {
+                    last_build_status: BuildStatus::Failure,
+                    ..cctray_project("web/build", "ppl2", "wf2", "1970-01-01T00:35:00+00:00")
+                },
+                cctray_project("api/build", "ppl1", "wf1", "1970-01-01T00:18:20+00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_pipelines_by_workflow_branch_including_temporary_names() {
+        let sem_pipelines = vec![
+            Pipeline {
+                result: Some(FAILED),
+                ..pipeline("Pipeline", "ppl3", "wf3", 3000)
+            },
+            pipeline("build", "ppl2", "wf2", 2000),
+            pipeline("build", "ppl1", "wf1", 1000),
+        ];
+        let workflows = vec![
+            Workflow { wf_id: String::from("wf3"), branch_name: String::from("main") },
+            Workflow { wf_id: String::from("wf2"), branch_name: String::from("feature") },
+            Workflow { wf_id: String::from("wf1"), branch_name: String::from("main") },
+        ];
+
+        let org = String::from("org-name");
+        let cctray_projects = to_cctray_project_info_by_workflow(sem_pipelines, workflows, &org, &FeedOptions::default());
+
+        assert_eq!(
+            cctray_projects,
+            vec![
+                CCTrayProjectInfo {
+                    last_build_status: BuildStatus::Failure,
+                    ..cctray_project("main", "ppl3", "wf3", "1970-01-01T00:51:40+00:00")
+                },
+                cctray_project("feature", "ppl2", "wf2", "1970-01-01T00:35:00+00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn returns_one_cctray_project_per_pipeline_name_and_branch() {
+        let sem_pipelines = vec![
+            Pipeline {
+                result: Some(FAILED),
+                branch_name: Some(String::from("feature")),
+                ..pipeline("build", "ppl2", "wf2", 2000)
+            },
+            Pipeline {
+                branch_name: Some(String::from("main")),
+                ..pipeline("build", "ppl1", "wf1", 1000)
+            },
+        ];
+
+        let org = String::from("org-name");
+        let cctray_projects = to_cctray_project_info_by_pipeline_and_branch(sem_pipelines, &org, &FeedOptions::default());
+
+        assert_eq!(
+            cctray_projects,
+            vec![
+                CCTrayProjectInfo {
+                    last_build_status: BuildStatus::Failure,
+                    ..cctray_project("build [feature]", "ppl2", "wf2", "1970-01-01T00:35:00+00:00")
+                },
+                cctray_project("build [main]", "ppl1", "wf1", "1970-01-01T00:18:20+00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_queued_pipelines_as_checking_modifications() {
+        let sem_pipelines = vec![
+            Pipeline {
+                state: State::QUEUING,
+                result: None,
+                done_at: Timestamp { seconds: 0 },
+                ..pipeline("foo", "ppl2", "wf2", 2000)
+            },
+            pipeline("foo", "ppl1", "wf1", 1000),
+        ];
+
+        let org = String::from("org-name");
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
+
+        assert_eq!(cctray_projects[0].activity, Activity::CheckingModifications);
+        assert_eq!(cctray_projects[0].last_build_status, BuildStatus::Success);
+    }
+
+    #[test]
+    fn reports_stopping_pipelines_as_building() {
+        let sem_pipelines = vec![Pipeline {
+            state: State::STOPPING,
+            result: None,
+            done_at: Timestamp { seconds: 0 },
+            ..pipeline("foo", "ppl1", "wf1", 1000)
+        }];
+
+        let org = String::from("org-name");
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
+
+        assert_eq!(cctray_projects[0].activity, Activity::Building);
+    }
+
+    #[test]
+    fn reports_stopped_and_canceled_pipelines_as_exceptions_by_default() {
+        let org = String::from("org-name");
+        let options = FeedOptions::default();
+
+        let stopped = to_cctray_project_info(vec![completed_pipeline(STOPPED, None)], &org, &options);
+        let canceled = to_cctray_project_info(vec![completed_pipeline(CANCELED, None)], &org, &options);
+
+        assert_eq!(stopped[0].last_build_status, BuildStatus::Exception);
+        assert_eq!(canceled[0].last_build_status, BuildStatus::Exception);
+    }
+
+    #[test]
+    fn reports_errored_pipelines_as_exceptions_by_default() {
+        let org = String::from("org-name");
+        let options = FeedOptions::default();
+
+        let malformed = to_cctray_project_info(
+            vec![completed_pipeline(FAILED, Some(ResultReason::MALFORMED))],
+            &org,
+            &options,
+        );
+        let failed_tests = to_cctray_project_info(
+            vec![completed_pipeline(FAILED, Some(ResultReason::TEST))],
+            &org,
+            &options,
+        );
+
+        assert_eq!(malformed[0].last_build_status, BuildStatus::Exception);
+        assert_eq!(failed_tests[0].last_build_status, BuildStatus::Failure);
+    }
+
+    #[test]
+    fn reports_errored_pipelines_with_the_configured_status() {
+        let org = String::from("org-name");
+        let options = FeedOptions {
+            stopped_status: BuildStatus::Failure,
+            errored_status: BuildStatus::Exception,
+            ..FeedOptions::default()
+        };
+
+        let stopped = to_cctray_project_info(vec![completed_pipeline(STOPPED, None)], &org, &options);
+        let internal = to_cctray_project_info(
+            vec![completed_pipeline(STOPPED, Some(ResultReason::INTERNAL))],
+            &org,
+            &options,
+        );
+
+        assert_eq!(stopped[0].last_build_status, BuildStatus::Failure);
+        assert_eq!(internal[0].last_build_status, BuildStatus::Exception);
+    }
+
+    #[test]
+    fn reports_superseded_pipelines_with_the_status_of_the_previous_build() {
+        let org = String::from("org-name");
+        let superseded = |result, reason| Pipeline {
+            ppl_id: String::from("ppl2"),
+            created_at: Timestamp { seconds: 2000 },
+            done_at: Timestamp { seconds: 2100 },
+            ..completed_pipeline(result, Some(reason))
+        };
+        let status = |pipelines: Vec<Pipeline>| {
+            let cctray_projects = to_cctray_project_info(pipelines, &org, &FeedOptions::default());
+            (cctray_projects[0].last_build_status, cctray_projects[0].last_build_label.clone())
+        };
+
+        assert_eq!(
+            status(vec![superseded(STOPPED, ResultReason::USER), completed_pipeline(FAILED, None)]),
+            (BuildStatus::Failure, String::from("ppl1"))
+        );
+        assert_eq!(
+            status(vec![superseded(CANCELED, ResultReason::STRATEGY), completed_pipeline(PASSED, None)]),
+            (BuildStatus::Success, String::from("ppl1"))
+        );
+        assert_eq!(
+            status(vec![superseded(CANCELED, ResultReason::STRATEGY)]),
+            (BuildStatus::Exception, String::from("ppl2"))
+        );
+    }
+
+    fn parse_projects(xml: &str) -> Vec<Vec<(String, String)>> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        let mut projects = Vec::new();
+
+        loop {
+            match reader.read_event().unwrap() {
+                quick_xml::events::Event::Empty(e) if e.name().as_ref() == b"Project" => {
+                    projects.push(
+                        e.attributes()
+                            .map(|a| {
+                                let a = a.unwrap();
+                                (
+                                    String::from_utf8(a.key.as_ref().to_vec()).unwrap(),
+                                    a.unescape_value().unwrap().to_string(),
+                                )
+                            })
+                            .collect(),
+                    );
+                }
+                quick_xml::events::Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        projects
+    }
+
+    #[test]
+    fn serializes_projects_with_an_xml_declaration() {
+        let xml = serialize(vec![]);
+
+        assert_eq!(xml, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects></Projects>");
+    }
+
+    #[test]
+    fn escapes_attribute_values_when_serializing() {
+        let info = CCTrayProjectInfo {
+            last_build_label: String::from("it's done"),
+            web_url: String::from("https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1&a=b"),
+            ..cctray_project("Build & \"Deploy\" <prod>", "ppl1", "wf1", "1970-01-01T00:18:20+00:00")
+        };
+
+        let xml = serialize(vec![info]);
+
+        assert_eq!(
+            parse_projects(&xml),
+            vec![vec![
+                (String::from("name"), String::from("Build & \"Deploy\" <prod>")),
+                (String::from("activity"), String::from("Sleeping")),
+                (String::from("lastBuildStatus"), String::from("Success")),
+                (String::from("lastBuildLabel"), String::from("it's done")),
+                (String::from("lastBuildTime"), String::from("1970-01-01T00:18:20+00:00")),
+                (
+                    String::from("webUrl"),
+                    String::from("https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1&a=b")
+                ),
+            ]]
+        );
+    }
+
+    #[test]
+    fn serialized_projects_can_be_parsed_back() {
+        let org = String::from("org-name");
+        let sem_pipelines = vec![
+            completed_pipeline(PASSED, None),
+            Pipeline {
+                name: String::from("bar"),
+                ..completed_pipeline(FAILED, None)
+            },
+        ];
+        let cctray_projects = to_cctray_project_info(sem_pipelines, &org, &FeedOptions::default());
+        let names: Vec<String> = cctray_projects.iter().map(|i| i.name.clone()).collect();
+
+        let parsed_names: Vec<String> = parse_projects(&serialize(cctray_projects))
+            .into_iter()
+            .map(|attributes| attributes[0].1.clone())
+            .collect();
+
+        assert_eq!(parsed_names, names);
+    }
+
+    #[test]
+    fn serializes_projects_as_json() {
+        let org = String::from("org-name");
+        let cctray_projects = to_cctray_project_info(
+            vec![completed_pipeline(PASSED, None)],
+            &org,
+            &FeedOptions::default(),
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&serialize_json(cctray_projects)).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "projects": [{
+                    "name": "foo",
+                    "activity": "Sleeping",
+                    "lastBuildStatus": "Success",
+                    "lastBuildLabel": "ppl1",
+                    "lastBuildTime": "1970-01-01T00:18:20+00:00",
+                    "webUrl": "https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1",
+                    "workflowId": "wf1",
+                    "pipelineId": "ppl1"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn filters_projects_by_name() {
+        let org = String::from("org-name");
+        let pipelines = ["build", "deploy-staging", "deploy-production", "nightly-tests"]
+            .into_iter()
+            .map(|name| Pipeline {
+                name: name.to_string(),
+                ..completed_pipeline(PASSED, None)
+            })
+            .collect();
+        let cctray_projects = to_cctray_project_info(pipelines, &org, &FeedOptions::default());
+
+        let names = |include: &str, exclude: &str| -> Vec<String> {
+            filter_by_name(
+                cctray_projects.clone(),
+                &NamePattern::parse_list(include).unwrap(),
+                &NamePattern::parse_list(exclude).unwrap(),
+            )
+            .into_iter()
+            .map(|p| p.name)
+            .sorted()
+            .collect()
+        };
+
+        assert_eq!(names("", ""), ["build", "deploy-production", "deploy-staging", "nightly-tests"]);
+        assert_eq!(names("deploy-*", ""), ["deploy-production", "deploy-staging"]);
+        assert_eq!(names("deploy-*", "*-staging"), ["deploy-production"]);
+        assert_eq!(names("", "/^(build|nightly-.*)$/"), ["deploy-production", "deploy-staging"]);
+    }
+
+    #[test]
+    fn names_projects_with_the_name_template() {
+        let org = String::from("org-name");
+        let pipelines = vec![
+            Pipeline {
+                branch_name: Some(String::from("main")),
+                ..completed_pipeline(PASSED, None)
+            },
+            Pipeline {
+                name: String::from("Pipeline"),
+                ..completed_pipeline(PASSED, None)
+            },
+        ];
+        let options = FeedOptions {
+            name_template: Some("{project}:{branch}:{pipeline} ({name})".parse().unwrap()),
+            project: String::from("api"),
+            ..FeedOptions::default()
+        };
+
+        let cctray_projects = to_cctray_project_info_by_pipeline_and_branch(pipelines, &org, &options);
+
+        assert_eq!(cctray_projects.len(), 1);
+        assert_eq!(cctray_projects[0].name, "api:main:foo (foo [main])");
+    }
+
+    fn pipeline_details() -> PipelineDetails {
+        serde_json::from_value(serde_json::json!({
+            "pipeline": {
+                "ppl_id": "ppl1",
+                "commit_sha": "4f5e6d7c",
+                "commit_author": "alice",
+                "commit_message": "Upgrade dependencies"
+            },
+            "blocks": [
+                {"name": "Lint", "result": "PASSED"},
+                {"name": "Unit tests", "result": "FAILED"},
+                {"name": "Docker build", "result": "STOPPED"},
+                {"name": "Deploy"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn adds_breakers_and_failing_tasks_messages_to_broken_builds() {
+        let org = String::from("org-name");
+        let mut failed = to_cctray_project_info(vec![completed_pipeline(FAILED, None)], &org, &FeedOptions::default());
+        let mut passed = to_cctray_project_info(vec![completed_pipeline(PASSED, None)], &org, &FeedOptions::default());
+
+        add_messages(&mut failed[0], &pipeline_details());
+        add_messages(&mut passed[0], &pipeline_details());
+
+        assert_eq!(
+            failed[0].messages,
+            vec![
+                Message {
+                    kind: MessageKind::Breakers,
+                    text: String::from("alice"),
+                },
+                Message {
+                    kind: MessageKind::FailingTasks,
+                    text: String::from("Unit tests, Docker build"),
+                },
+            ]
+        );
+        assert_eq!(
+            failed[0].breaking_commit,
+            Some(Commit {
+                sha: String::from("4f5e6d7c"),
+                author: Some(String::from("alice")),
+                message: Some(String::from("Upgrade dependencies")),
+            })
+        );
+        assert!(passed[0].messages.is_empty());
+        assert_eq!(passed[0].breaking_commit, None);
+    }
+
+    #[test]
+    fn leaves_the_breakers_out_when_the_commit_author_is_not_reported() {
+        let org = String::from("org-name");
+        let mut cctray_projects = to_cctray_project_info(vec![completed_pipeline(FAILED, None)], &org, &FeedOptions::default());
+        let mut details = pipeline_details();
+        details.pipeline.commit_author = None;
+
+        add_messages(&mut cctray_projects[0], &details);
+
+        assert!(cctray_projects[0].messages.iter().all(|m| m.kind != MessageKind::Breakers));
+        let json: serde_json::Value = serde_json::from_str(&serialize_json(cctray_projects)).unwrap();
+        assert_eq!(
+            json["projects"][0]["breakingCommit"],
+            serde_json::json!({"sha": "4f5e6d7c", "author": null, "message": "Upgrade dependencies"})
+        );
+    }
+
+    #[test]
+    fn serializes_category_and_messages() {
+        let org = String::from("org-name");
+        let options = FeedOptions {
+            project: String::from("my-project"),
+            ..FeedOptions::default()
+        };
+        let mut cctray_projects = to_cctray_project_info(vec![completed_pipeline(FAILED, None)], &org, &options);
+        add_messages(&mut cctray_projects[0], &pipeline_details());
+
+        let xml = serialize(cctray_projects.clone());
+        let json: serde_json::Value = serde_json::from_str(&serialize_json(cctray_projects)).unwrap();
+
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"foo\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"ppl1\" lastBuildTime=\"1970-01-01T00:18:20+00:00\" webUrl=\"https://org-name.semaphoreci.com/workflows/wf1?pipeline_id=ppl1\" category=\"my-project\"><messages><message kind=\"Breakers\" text=\"alice\"/><message kind=\"FailingTasks\" text=\"Unit tests, Docker build\"/></messages></Project></Projects>"
+        );
+        assert_eq!(json["projects"][0]["category"], "my-project");
+        assert_eq!(
+            json["projects"][0]["messages"],
+            serde_json::json!([
+                {"kind": "Breakers", "text": "alice"},
+                {"kind": "FailingTasks", "text": "Unit tests, Docker build"}
+            ])
+        );
+        assert_eq!(
+            json["projects"][0]["breakingCommit"],
+            serde_json::json!({"sha": "4f5e6d7c", "author": "alice", "message": "Upgrade dependencies"})
+        );
+    }
+
+    #[test]
+    fn labels_builds_with_the_label_template_falling_back_to_the_pipeline_id() {
+        let org = String::from("org-name");
+        let branch_pipeline = |name: &str, branch: &str, commit_sha: Option<&str>| Pipeline {
+            branch_name: Some(branch.to_string()),
+            commit_sha: commit_sha.map(String::from),
+            ..pipeline(name, "ppl1", "wf1", 1000)
+        };
+        let labels = |label: &str| -> Vec<(String, String)> {
+            let options = FeedOptions {
+                label_template: Some(label.parse().unwrap()),
+                ..FeedOptions::default()
+            };
+            let pipelines = vec![
+                branch_pipeline("build", "main", Some("4f5e6d7c8b9a")),
+                branch_pipeline("release", "refs/tags/v1.2.0", Some("0a1b2c3d4e5f")),
+                branch_pipeline("deploy", "main", None),
+            ];
+
+            to_cctray_project_info(pipelines, &org, &options)
+                .into_iter()
+                .map(|p| (p.name, p.last_build_label))
+                .sorted()
+                .collect()
+        };
+        let expected = |build: &str, deploy: &str, release: &str| {
+            vec![
+                (String::from("build"), build.to_string()),
+                (String::from("deploy"), deploy.to_string()),
+                (String::from("release"), release.to_string()),
+            ]
+        };
+
+        assert_eq!(labels("sha"), expected("4f5e6d7", "ppl1", "0a1b2c3"));
+        assert_eq!(labels("branch"), expected("main", "main", "ppl1"));
+        assert_eq!(labels("tag"), expected("ppl1", "ppl1", "v1.2.0"));
+        assert_eq!(labels("{branch}@{sha}"), expected("main@4f5e6d7", "ppl1", "ppl1"));
+        assert_eq!(labels("workflow_number"), expected("ppl1", "ppl1", "ppl1"));
+    }
+
+    fn split_pipelines() -> (Vec<CCTrayProjectInfo>, HashMap<String, PipelineDetails>) {
+        let running = Pipeline {
+            state: State::RUNNING,
+            result: None,
+            ..pipeline("foo", "ppl2", "wf1", 2000)
+        };
+        let cctray_projects = to_cctray_project_info(
+            vec![completed_pipeline(FAILED, None), running],
+            "org-name",
+            &FeedOptions::default(),
+        );
+
+        let details = |value: serde_json::Value| serde_json::from_value::<PipelineDetails>(value).unwrap();
+        let details = HashMap::from([
+            (
+                String::from("ppl1"),
+                details(serde_json::json!({
+                    "pipeline": {"state": "DONE"},
+                    "blocks": [
+                        {"name": "Lint", "state": "DONE", "result": "PASSED", "jobs": [
+                            {"name": "clippy", "status": "FINISHED", "result": "PASSED"}
+                        ]},
+                        {"name": "Unit tests", "state": "DONE", "result": "FAILED", "jobs": [
+                            {"name": "rspec 1/2", "status": "FINISHED", "result": "PASSED"},
+                            {"name": "rspec 2/2", "status": "FINISHED", "result": "FAILED"}
+                        ]}
+                    ]
+                })),
+            ),
+            (
+                String::from("ppl2"),
+                details(serde_json::json!({
+                    "pipeline": {"state": "RUNNING"},
+                    "blocks": [
+                        {"name": "Lint", "state": "DONE", "result": "PASSED", "jobs": [
+                            {"name": "clippy", "status": "FINISHED", "result": "PASSED"}
+                        ]},
+                        {"name": "Unit tests", "state": "RUNNING", "jobs": [
+                            {"name": "rspec 1/2", "status": "RUNNING"},
+                            {"name": "rspec 2/2", "status": "QUEUED"}
+                        ]},
+                        {"name": "Deploy", "state": "WAITING"}
+                    ]
+                })),
+            ),
+        ]);
+
+        (cctray_projects, details)
+    }
+
+    #[test]
+    fn splits_projects_per_block() {
+        let (cctray_projects, details) = split_pipelines();
+
+        let blocks = split_by_task(cctray_projects, &details, false, &FeedOptions::default());
+
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|p| (p.name.as_str(), p.activity.clone(), p.last_build_status))
+                .collect_vec(),
+            vec![
+                ("foo :: Lint", Activity::Sleeping, BuildStatus::Success),
+                ("foo :: Unit tests", Activity::Building, BuildStatus::Failure),
+                ("foo :: Deploy", Activity::CheckingModifications, BuildStatus::Unknown),
+            ]
+        );
+        assert!(blocks.iter().all(|p| p.pipeline_id == "ppl2"));
+        assert!(blocks.iter().all(|p| p.last_build_pipeline_id.as_deref() == Some("ppl1")));
+    }
+
+    #[test]
+    fn splits_projects_per_job() {
+        let (cctray_projects, details) = split_pipelines();
+
+        let jobs = split_by_task(cctray_projects, &details, true, &FeedOptions::default());
+
+        assert_eq!(
+            jobs.iter()
+                .map(|p| (p.name.as_str(), p.activity.clone(), p.last_build_status))
+                .collect_vec(),
+            vec![
+                ("foo :: Lint :: clippy", Activity::Sleeping, BuildStatus::Success),
+                ("foo :: Unit tests :: rspec 1/2", Activity::Building, BuildStatus::Success),
+                ("foo :: Unit tests :: rspec 2/2", Activity::CheckingModifications, BuildStatus::Failure),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_projects_without_known_blocks() {
+        let (cctray_projects, _) = split_pipelines();
+
+        let split = split_by_task(cctray_projects.clone(), &HashMap::new(), false, &FeedOptions::default());
+
+        assert_eq!(split, cctray_projects);
+    }
+
+    fn promoted_pipelines() -> Vec<Pipeline> {
+        let promotion = |name: &str, ppl_id: &str, promotion_of: Option<&str>, created_at: i64| Pipeline {
+            branch_name: Some(String::from("main")),
+            promotion_of: promotion_of.map(String::from),
+            ..pipeline(name, ppl_id, "wf1", created_at)
+        };
+
+        vec![
+            promotion("build", "ppl1", Some(""), 1000),
+            promotion("deploy-staging", "ppl2", Some("ppl1"), 1200),
+            promotion("deploy-production", "ppl3", Some("ppl2"), 1400),
+            promotion("build", "ppl4", None, 2000),
+            promotion("deploy-staging", "ppl5", Some("ppl4"), 2200),
+            promotion("lint", "ppl6", Some("unknown-ppl"), 2400),
+        ]
+    }
+
+    #[test]
+    fn names_promotions_after_their_parent_pipeline() {
+        let cctray_projects = to_cctray_project_info(promoted_pipelines(), "org-name", &FeedOptions::default());
+
+        assert_eq!(
+            cctray_projects.iter().map(|p| (p.name.as_str(), p.pipeline_id.as_str())).collect_vec(),
+            vec![
+                ("lint", "ppl6"),
+                ("build → deploy-staging", "ppl5"),
+                ("build", "ppl4"),
+                ("build → deploy-staging → deploy-production", "ppl3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn hides_stale_promotions_not_triggered_from_the_latest_parent_pipeline() {
+        let options = FeedOptions {
+            hide_stale_promotions: true,
+            ..FeedOptions::default()
+        };
+
+        let cctray_projects = to_cctray_project_info(promoted_pipelines(), "org-name", &options);
+
+        assert_eq!(
+            cctray_projects.iter().map(|p| p.name.as_str()).collect_vec(),
+            vec!["lint", "build → deploy-staging", "build"]
+        );
+    }
+
+    #[test]
+    fn hides_stale_promotions_of_an_older_build_until_the_latest_one_is_promoted() {
+        let options = FeedOptions {
+            hide_stale_promotions: true,
+            ..FeedOptions::default()
+        };
+        let names = |latest_build_promoted: bool| {
+            let mut pipelines = vec![
+                pipeline("build", "ppl1", "wf1", 1000),
+                Pipeline {
+                    promotion_of: Some(String::from("ppl1")),
+                    ..pipeline("deploy", "ppl2", "wf1", 1200)
+                },
+                pipeline("build", "ppl3", "wf2", 2000),
+            ];
+            if latest_build_promoted {
+                pipelines.push(Pipeline {
+                    promotion_of: Some(String::from("ppl3")),
+                    ..pipeline("deploy", "ppl4", "wf2", 2200)
+                });
+            }
+
+            to_cctray_project_info(pipelines, "org-name", &options)
+                .into_iter()
+                .map(|p| p.name)
+                .collect_vec()
+        };
+
+        assert_eq!(names(false), ["build"]);
+        assert_eq!(names(true), ["build → deploy", "build"]);
+    }
 }
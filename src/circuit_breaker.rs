@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/*
+ * Stops calling a SemaphoreCI host after `threshold` consecutive failures, for `cooldown`, so
+ * that requests fail fast instead of piling up while it is down. Once the cooldown has passed,
+ * requests go through again: a success closes the circuit, a failure opens it straight away.
+ *
+ * Circuits are tracked per host, as each organisation has its own SemaphoreCI URL.
+ */
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether requests to the host may be sent.
+    pub fn allows(&self, host: &str) -> bool {
+        match self.circuits.lock().unwrap().get(host) {
+            Some(Circuit {
+                open_until: Some(open_until),
+                ..
+            }) => *open_until <= Instant::now(),
+            _ => true,
+        }
+    }
+
+    pub fn record_success(&self, host: &str) {
+        self.circuits.lock().unwrap().remove(host);
+    }
+
+    pub fn record_failure(&self, host: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(host.to_string()).or_default();
+
+        circuit.consecutive_failures += 1;
+        if circuit.consecutive_failures >= self.threshold {
+            circuit.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure("org.semaphoreci.com");
+        assert!(breaker.allows("org.semaphoreci.com"));
+
+        breaker.record_failure("org.semaphoreci.com");
+        assert!(!breaker.allows("org.semaphoreci.com"));
+        assert!(breaker.allows("other-org.semaphoreci.com"));
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure("org.semaphoreci.com");
+        breaker.record_success("org.semaphoreci.com");
+        breaker.record_failure("org.semaphoreci.com");
+
+        assert!(breaker.allows("org.semaphoreci.com"));
+    }
+
+    #[test]
+    fn allows_requests_again_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+
+        breaker.record_failure("org.semaphoreci.com");
+
+        assert!(breaker.allows("org.semaphoreci.com"));
+    }
+
+    #[test]
+    fn never_opens_with_zero_threshold() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(60));
+
+        breaker.record_failure("org.semaphoreci.com");
+
+        assert!(breaker.allows("org.semaphoreci.com"));
+    }
+}
@@ -0,0 +1,1262 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Env var holding the path of the configuration file, when not given on the command line.
+pub const CONFIG_PATH_ENV_VAR: &str = "SEMAPHORECI_CCTRAY_CONFIG";
+
+const REDACTED: &str = "<redacted>";
+
+/*
+ * The application configuration, read from a TOML file whose sections mirror the structs below.
+ * Any setting can be overridden with an env var (see `Config::apply_env`), which is convenient when
+ * running in a container.
+ */
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub upstream: UpstreamConfig,
+    pub cache: CacheConfig,
+    pub feed: FeedConfig,
+    pub auth: AuthConfig,
+    pub health: HealthConfig,
+    pub fallback: FallbackConfig,
+    pub poller: PollerConfig,
+    pub webhook: WebhookConfig,
+    pub events: EventsConfig,
+    /// Settings of SemaphoreCI projects, by `org/project`, the project being identified by its
+    /// name or id.
+    pub projects: HashMap<String, ProjectConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// IP address, or host name such as `localhost`, to bind the server to.
+    pub bind_ip: String,
+    /// TCP port to bind the server to.
+    pub port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_ip: String::from("127.0.0.1"),
+            port: 8080,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    /// Overrides the SemaphoreCI API base URL, which otherwise is derived from the organisation.
+    pub base_url: Option<String>,
+    /// Maximum number of pages followed when listing projects.
+    pub max_pages: usize,
+    /// Maximum number of pages followed when listing the pipelines or workflows of a project.
+    /// Feeds only need the latest runs, which are listed first.
+    pub max_pipeline_pages: usize,
+    /// Maximum number of projects, or pipeline details, fetched at the same time for a feed.
+    pub max_concurrent_requests: usize,
+    /// How long connecting to SemaphoreCI may take.
+    #[serde(rename = "connect_timeout_seconds", with = "seconds")]
+    pub connect_timeout: Duration,
+    /// How long SemaphoreCI may go without sending any data while responding.
+    #[serde(rename = "read_timeout_seconds", with = "seconds")]
+    pub read_timeout: Duration,
+    /// How many times requests answered with a 5xx or 429 status are retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each following one. A `Retry-After` header
+    /// takes precedence.
+    #[serde(rename = "retry_backoff_millis", with = "millis")]
+    pub retry_backoff: Duration,
+    /// Longest delay before a retry, including delays requested with `Retry-After`.
+    #[serde(rename = "max_retry_delay_seconds", with = "seconds")]
+    pub max_retry_delay: Duration,
+    /// Consecutive failed requests after which SemaphoreCI isn't called anymore, until
+    /// `circuit_breaker_cooldown` has passed. Zero disables the circuit breaker.
+    pub circuit_breaker_threshold: u32,
+    #[serde(rename = "circuit_breaker_cooldown_seconds", with = "seconds")]
+    pub circuit_breaker_cooldown: Duration,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        UpstreamConfig {
+            base_url: None,
+            max_pages: 10,
+            max_pipeline_pages: 1,
+            max_concurrent_requests: 4,
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(30),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(250),
+            max_retry_delay: Duration::from_secs(10),
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long upstream responses are served from the cache. Zero disables the cache.
+    #[serde(rename = "ttl_seconds", with = "seconds")]
+    pub ttl: Duration,
+    /// How long expired responses are still served, while being refreshed in the background.
+    #[serde(rename = "stale_while_revalidate_seconds", with = "seconds")]
+    pub stale_while_revalidate: Duration,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedConfig {
+    /// cctray build status of pipelines stopped or canceled before completing. Pipelines stopped
+    /// by a user or auto-cancelled by a newer pipeline keep the status of the build before them.
+    pub stopped_status: ErrorStatus,
+    /// cctray build status of pipelines which didn't complete because of an error rather than a
+    /// failing build, eg. because of an invalid yaml file, whether SemaphoreCI reports them as
+    /// failed or stopped.
+    pub errored_status: ErrorStatus,
+    /// Template cctray projects are named with, instead of their default name.
+    pub name_template: Option<NameTemplate>,
+    /// Source of the last build labels, eg. `sha` or `{branch}@{sha}`, instead of the pipeline
+    /// id.
+    pub label: Option<LabelTemplate>,
+    /// Leaves out the stale promotions, ie. the ones which the latest run of their parent pipeline
+    /// didn't trigger, eg. until a new build is deployed.
+    pub hide_stale_promotions: bool,
+}
+
+/// The cctray build statuses pipelines which didn't complete normally can be reported with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum ErrorStatus {
+    #[default]
+    Exception,
+    Failure,
+}
+
+impl FromStr for ErrorStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Exception" => Ok(ErrorStatus::Exception),
+            "Failure" => Ok(ErrorStatus::Failure),
+            _ => Err(format!("Invalid build status {}, expected Exception or Failure", s)),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// SemaphoreCI API tokens by organisation, used when requests don't have an `Authorization`
+    /// header.
+    pub tokens: HashMap<String, String>,
+    /// File with additional tokens, as `org=token` lines. Tokens set in `tokens` take precedence.
+    pub tokens_file: Option<PathBuf>,
+    /// Whether the token in the `Authorization` header of requests is used. When disabled, only
+    /// the configured tokens are used.
+    pub header_passthrough: bool,
+}
+
+impl AuthConfig {
+    /// Parses `org=token` pairs, separated by commas or new lines. Blank lines and lines starting
+    /// with `#` are ignored.
+    pub fn parse_tokens(s: &str) -> Result<HashMap<String, String>, String> {
+        s.split([',', '\n'])
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+            .map(|entry| match entry.split_once('=') {
+                Some((org, token)) if !org.trim().is_empty() && !token.trim().is_empty() => {
+                    Ok((org.trim().to_string(), token.trim().to_string()))
+                }
+                _ => Err(format!("Invalid token entry {}, expected org=token", entry)),
+            })
+            .collect()
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            tokens: HashMap::new(),
+            tokens_file: None,
+            header_passthrough: true,
+        }
+    }
+}
+
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("tokens", &self.tokens.keys().collect::<Vec<_>>())
+            .field("tokens_file", &self.tokens_file)
+            .field("header_passthrough", &self.header_passthrough)
+            .finish()
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    /// SemaphoreCI API token the readiness check lists projects with. Without a token, the check
+    /// only verifies that `upstream.base_url` responds.
+    pub token: Option<String>,
+    /// How long the readiness check waits for SemaphoreCI to respond.
+    #[serde(rename = "timeout_seconds", with = "seconds")]
+    pub timeout: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            token: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl fmt::Debug for HealthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HealthConfig")
+            .field("token", &self.token.as_ref().map(|_| REDACTED))
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FallbackConfig {
+    /// How old the last good feed may be to be served when SemaphoreCI is unavailable. Zero
+    /// disables the fallback.
+    #[serde(rename = "max_staleness_seconds", with = "seconds")]
+    pub max_staleness: Duration,
+    /// Whether the projects of a fallback feed are reported with an `Unknown` build status,
+    /// rather than their last known one.
+    pub unknown_status: bool,
+}
+
+impl Default for FallbackConfig {
+    fn default() -> Self {
+        FallbackConfig {
+            max_staleness: Duration::from_secs(3600),
+            unknown_status: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollerConfig {
+    /// Projects whose feed is fetched in the background, and served from memory. Each needs a
+    /// token configured for its organisation.
+    pub targets: Vec<PollTarget>,
+    /// How often the projects are polled.
+    #[serde(rename = "interval_seconds", with = "seconds")]
+    pub interval: Duration,
+}
+
+impl Default for PollerConfig {
+    fn default() -> Self {
+        PollerConfig {
+            targets: Vec::new(),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A SemaphoreCI project, written `org/project`. The project is identified by its name or id.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PollTarget {
+    pub org: String,
+    pub project: String,
+}
+
+impl FromStr for PollTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('/') {
+            Some((org, project)) if !org.is_empty() && !project.is_empty() && !project.contains('/') => {
+                Ok(PollTarget {
+                    org: org.to_string(),
+                    project: project.to_string(),
+                })
+            }
+            _ => Err(format!("Invalid poller target {}, expected org/project", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for PollTarget {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PollTarget> for String {
+    fn from(target: PollTarget) -> Self {
+        format!("{}/{}", target.org, target.project)
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Secret SemaphoreCI signs webhook notifications with. Webhooks are disabled without one.
+    pub secret: Option<String>,
+}
+
+impl fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("secret", &self.secret.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsConfig {
+    /// How often the feed of a project is checked for changes while clients are listening to
+    /// its events.
+    #[serde(rename = "interval_seconds", with = "seconds")]
+    pub interval: Duration,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        EventsConfig {
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Patterns of the cctray projects in the feed, unless the request has an `include`
+    /// parameter. All are included when empty.
+    pub include: Vec<NamePattern>,
+    /// Patterns of the cctray projects left out of the feed, unless the request has an `exclude`
+    /// parameter.
+    pub exclude: Vec<NamePattern>,
+    /// Template the cctray projects are named with, unless the request has a `name` parameter.
+    /// Overrides `feed.name_template`.
+    pub name_template: Option<NameTemplate>,
+    /// Source of the last build labels, unless the request has a `label` parameter. Overrides
+    /// `feed.label`.
+    pub label: Option<LabelTemplate>,
+}
+
+/// A pattern matching cctray project names: a glob, where `*` matches any characters and `?` a
+/// single one, or a regular expression between slashes, eg. `/^deploy-(staging|production)$/`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NamePattern {
+    source: String,
+    regex: Regex,
+}
+
+impl NamePattern {
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Parses comma separated patterns.
+    pub fn parse_list(s: &str) -> Result<Vec<NamePattern>, String> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = match s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            Some(regex) => Regex::new(regex),
+            None => {
+                let glob = s
+                    .chars()
+                    .map(|c| match c {
+                        '*' => String::from(".*"),
+                        '?' => String::from("."),
+                        c => regex::escape(&c.to_string()),
+                    })
+                    .collect::<String>();
+                Regex::new(&format!("^{}$", glob))
+            }
+        };
+
+        regex
+            .map(|regex| NamePattern {
+                source: s.to_string(),
+                regex,
+            })
+            .map_err(|e| format!("Invalid pattern {}: {}", s, e))
+    }
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl TryFrom<String> for NamePattern {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NamePattern> for String {
+    fn from(pattern: NamePattern) -> Self {
+        pattern.source
+    }
+}
+
+/*
+ * A template referring to values by placeholder, eg. `{project}/{pipeline}`. Placeholders are
+ * checked when the template is parsed, against the ones `P` allows.
+ */
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String", bound = "")]
+pub struct Template<P: TemplatePlaceholder> {
+    source: String,
+    segments: Vec<Segment<P>>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment<P> {
+    Text(String),
+    Placeholder(P),
+}
+
+pub trait TemplatePlaceholder: Copy + fmt::Debug + 'static {
+    const ALL: &'static [Self];
+    /// Whether a single placeholder can be given without braces, eg. `sha`. Otherwise, such a
+    /// template is plain text.
+    const BARE: bool = false;
+
+    fn as_str(&self) -> &'static str;
+}
+
+/// A template cctray project names are built from, eg. `{project}/{pipeline}`.
+pub type NameTemplate = Template<NamePlaceholder>;
+
+/// The values a `NameTemplate` can refer to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NamePlaceholder {
+    /// The SemaphoreCI organisation.
+    Org,
+    /// The SemaphoreCI project name.
+    Project,
+    /// The name of the latest pipeline.
+    Pipeline,
+    /// The branch of the latest pipeline, empty when it didn't run on a branch.
+    Branch,
+    /// The default name of the cctray project, eg. `build [main]` when grouping by pipeline and
+    /// branch.
+    Name,
+}
+
+impl TemplatePlaceholder for NamePlaceholder {
+    const ALL: &'static [Self] = &[
+        NamePlaceholder::Org,
+        NamePlaceholder::Project,
+        NamePlaceholder::Pipeline,
+        NamePlaceholder::Branch,
+        NamePlaceholder::Name,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            NamePlaceholder::Org => "org",
+            NamePlaceholder::Project => "project",
+            NamePlaceholder::Pipeline => "pipeline",
+            NamePlaceholder::Branch => "branch",
+            NamePlaceholder::Name => "name",
+        }
+    }
+}
+
+/*
+ * A template the last build label of cctray projects is built from, eg. `{branch}@{sha}`. A
+ * single placeholder can be given without braces, eg. `sha`.
+ */
+pub type LabelTemplate = Template<LabelPlaceholder>;
+
+/// The values a `LabelTemplate` can refer to, all of the last completed pipeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelPlaceholder {
+    /// The short SHA of the commit.
+    Sha,
+    /// The branch, unless the pipeline ran on a tag.
+    Branch,
+    /// The git tag.
+    Tag,
+    /// The number of the workflow, when SemaphoreCI reports it.
+    WorkflowNumber,
+    /// The SemaphoreCI pipeline id.
+    Id,
+}
+
+impl TemplatePlaceholder for LabelPlaceholder {
+    const ALL: &'static [Self] = &[
+        LabelPlaceholder::Sha,
+        LabelPlaceholder::Branch,
+        LabelPlaceholder::Tag,
+        LabelPlaceholder::WorkflowNumber,
+        LabelPlaceholder::Id,
+    ];
+    const BARE: bool = true;
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LabelPlaceholder::Sha => "sha",
+            LabelPlaceholder::Branch => "branch",
+            LabelPlaceholder::Tag => "tag",
+            LabelPlaceholder::WorkflowNumber => "workflow_number",
+            LabelPlaceholder::Id => "id",
+        }
+    }
+}
+
+impl<P: TemplatePlaceholder> Template<P> {
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, value: impl Fn(P) -> String) -> String {
+        self.try_render(|placeholder| Some(value(placeholder)))
+            .expect("All placeholders have a value")
+    }
+
+    /// Renders the template, unless one of its placeholders has no value.
+    pub fn try_render(&self, value: impl Fn(P) -> Option<String>) -> Option<String> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => Some(text.clone()),
+                Segment::Placeholder(placeholder) => value(*placeholder),
+            })
+            .collect()
+    }
+
+    fn parse_placeholder(name: &str) -> Option<P> {
+        P::ALL.iter().copied().find(|p| p.as_str() == name)
+    }
+}
+
+impl<P: TemplatePlaceholder> FromStr for Template<P> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(placeholder) = Self::parse_placeholder(s).filter(|_| P::BARE) {
+            return Ok(Template {
+                source: s.to_string(),
+                segments: vec![Segment::Placeholder(placeholder)],
+            });
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| format!("Invalid template {}: unclosed {{", s))?;
+            let name = &rest[start + 1..end];
+            let placeholder = Self::parse_placeholder(name)
+                .ok_or_else(|| format!("Invalid template {}: unknown placeholder {{{}}}", s, name))?;
+
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(Template {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl<P: TemplatePlaceholder> PartialEq for Template<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl<P: TemplatePlaceholder> TryFrom<String> for Template<P> {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl<P: TemplatePlaceholder> From<Template<P>> for String {
+    fn from(template: Template<P>) -> Self {
+        template.source
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Env {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "Cannot read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "Invalid config file {}: {}", path.display(), e),
+            ConfigError::Env {
+                name,
+                value,
+                reason,
+            } => write!(f, "Invalid value {:?} for {}: {}", value, name, reason),
+            ConfigError::Invalid(reason) => write!(f, "Invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Loads the configuration from an optional file, then applies the env var overrides. `env`
+    /// looks up env vars, so that tests don't depend on the process environment.
+    pub fn load(
+        path: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut config = match path {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        config.apply_env(env)?;
+        config.load_tokens_file()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_path_buf(), e))?;
+
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let env = |name: &'static str| env(name).map(|value| (name, value));
+
+        if let Some((_, value)) = env("BIND_IP") {
+            self.server.bind_ip = value;
+        }
+        if let Some(var) = env("PORT") {
+            self.server.port = parse_env(var)?;
+        }
+        if let Some((_, value)) = env("CI_BASE_URL") {
+            self.upstream.base_url = Some(value);
+        }
+        if let Some(var) = env("CI_MAX_PAGES") {
+            self.upstream.max_pages = parse_env(var)?;
+        }
+        if let Some(var) = env("CI_MAX_PIPELINE_PAGES") {
+            self.upstream.max_pipeline_pages = parse_env(var)?;
+        }
+        if let Some(var) = env("CI_MAX_CONCURRENT_REQUESTS") {
+            self.upstream.max_concurrent_requests = parse_env(var)?;
+        }
+        if let Some(var) = env("CI_CONNECT_TIMEOUT_SECONDS") {
+            self.upstream.connect_timeout = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("CI_READ_TIMEOUT_SECONDS") {
+            self.upstream.read_timeout = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("CI_MAX_RETRIES") {
+            self.upstream.max_retries = parse_env(var)?;
+        }
+        if let Some(var) = env("CI_RETRY_BACKOFF_MILLIS") {
+            self.upstream.retry_backoff = Duration::from_millis(parse_env(var)?);
+        }
+        if let Some(var) = env("CI_MAX_RETRY_DELAY_SECONDS") {
+            self.upstream.max_retry_delay = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("CI_CIRCUIT_BREAKER_THRESHOLD") {
+            self.upstream.circuit_breaker_threshold = parse_env(var)?;
+        }
+        if let Some(var) = env("CI_CIRCUIT_BREAKER_COOLDOWN_SECONDS") {
+            self.upstream.circuit_breaker_cooldown = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("CACHE_TTL_SECONDS") {
+            self.cache.ttl = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("CACHE_STALE_WHILE_REVALIDATE_SECONDS") {
+            self.cache.stale_while_revalidate = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("STOPPED_BUILD_STATUS") {
+            self.feed.stopped_status = parse_env(var)?;
+        }
+        if let Some(var) = env("ERRORED_BUILD_STATUS") {
+            self.feed.errored_status = parse_env(var)?;
+        }
+        if let Some(var) = env("FEED_NAME_TEMPLATE") {
+            self.feed.name_template = Some(parse_env(var)?);
+        }
+        if let Some(var) = env("FEED_LABEL") {
+            self.feed.label = Some(parse_env(var)?);
+        }
+        if let Some(var) = env("FEED_HIDE_STALE_PROMOTIONS") {
+            self.feed.hide_stale_promotions = parse_env(var)?;
+        }
+        if let Some((name, value)) = env("SEMAPHORE_TOKENS") {
+            let tokens = AuthConfig::parse_tokens(&value).map_err(|reason| ConfigError::Env {
+                name,
+                value: REDACTED.to_string(),
+                reason,
+            })?;
+            self.auth.tokens.extend(tokens);
+        }
+        if let Some((_, value)) = env("SEMAPHORE_TOKENS_FILE") {
+            self.auth.tokens_file = Some(PathBuf::from(value));
+        }
+        if let Some(var) = env("AUTH_HEADER_PASSTHROUGH") {
+            self.auth.header_passthrough = parse_env(var)?;
+        }
+        if let Some((_, value)) = env("HEALTH_CHECK_TOKEN") {
+            self.health.token = Some(value);
+        }
+        if let Some(var) = env("HEALTH_CHECK_TIMEOUT_SECONDS") {
+            self.health.timeout = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("FALLBACK_MAX_STALENESS_SECONDS") {
+            self.fallback.max_staleness = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some(var) = env("FALLBACK_UNKNOWN_STATUS") {
+            self.fallback.unknown_status = parse_env(var)?;
+        }
+        if let Some((name, value)) = env("POLLER_TARGETS") {
+            self.poller.targets = value
+                .split(',')
+                .filter(|target| !target.trim().is_empty())
+                .map(|target| parse_env((name, target.to_string())))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(var) = env("POLLER_INTERVAL_SECONDS") {
+            self.poller.interval = Duration::from_secs(parse_env(var)?);
+        }
+        if let Some((_, value)) = env("WEBHOOK_SECRET") {
+            self.webhook.secret = Some(value);
+        }
+        if let Some(var) = env("EVENTS_INTERVAL_SECONDS") {
+            self.events.interval = Duration::from_secs(parse_env(var)?);
+        }
+
+        Ok(())
+    }
+
+    fn load_tokens_file(&mut self) -> Result<(), ConfigError> {
+        if let Some(path) = &self.auth.tokens_file {
+            let content =
+                fs::read_to_string(path).map_err(|e| ConfigError::Read(path.clone(), e))?;
+            let mut tokens = AuthConfig::parse_tokens(&content).map_err(|reason| {
+                ConfigError::Invalid(format!("{} in {}", reason, path.display()))
+            })?;
+
+            tokens.extend(self.auth.tokens.drain());
+            self.auth.tokens = tokens;
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Err(e) = (self.server.bind_ip.as_str(), self.server.port).to_socket_addrs() {
+            return Err(ConfigError::Invalid(format!(
+                "server.bind_ip {} is not an IP address or a known host name: {}",
+                self.server.bind_ip, e
+            )));
+        }
+
+        if self.upstream.max_pages == 0
+            || self.upstream.max_pipeline_pages == 0
+            || self.upstream.max_concurrent_requests == 0
+        {
+            return Err(ConfigError::Invalid(String::from(
+                "upstream.max_pages, upstream.max_pipeline_pages and upstream.max_concurrent_requests must be at least 1",
+            )));
+        }
+
+        if self.upstream.connect_timeout.is_zero() || self.upstream.read_timeout.is_zero() {
+            return Err(ConfigError::Invalid(String::from(
+                "upstream.connect_timeout_seconds and upstream.read_timeout_seconds must be at least 1",
+            )));
+        }
+
+        if self.poller.interval.is_zero() || self.events.interval.is_zero() {
+            return Err(ConfigError::Invalid(String::from(
+                "poller.interval_seconds and events.interval_seconds must be at least 1",
+            )));
+        }
+
+        if let Some(target) = self
+            .poller
+            .targets
+            .iter()
+            .find(|target| !self.auth.tokens.contains_key(&target.org))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "poller target {}/{} has no token configured for its organisation",
+                target.org, target.project
+            )));
+        }
+
+        if let Some(key) = self.projects.keys().find(|key| key.parse::<PollTarget>().is_err()) {
+            return Err(ConfigError::Invalid(format!(
+                "projects.\"{}\" is not an org/project pair",
+                key
+            )));
+        }
+
+        if let Some(base_url) = &self.upstream.base_url {
+            match reqwest::Url::parse(base_url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => {
+                    return Err(ConfigError::Invalid(format!(
+                        "upstream.base_url {} is not an http(s) URL",
+                        base_url
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The configuration as TOML, with the tokens and secrets redacted.
+    pub fn to_redacted_toml(&self) -> String {
+        let mut config = self.clone();
+        config
+            .auth
+            .tokens
+            .values_mut()
+            .for_each(|token| *token = REDACTED.to_string());
+        if config.health.token.is_some() {
+            config.health.token = Some(REDACTED.to_string());
+        }
+        if config.webhook.secret.is_some() {
+            config.webhook.secret = Some(REDACTED.to_string());
+        }
+
+        toml::to_string_pretty(&config).expect("The configuration can always be serialised")
+    }
+}
+
+fn parse_env<T>((name, value): (&'static str, String)) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::Env {
+        name,
+        reason: e.to_string(),
+        value,
+    })
+}
+
+mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    fn write_config_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("semaphoreci-cctray-{}-{}", std::process::id(), name));
+        fs::File::create(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_tokens_separated_by_commas_or_new_lines() {
+        let tokens = AuthConfig::parse_tokens("org1=token1, org2 = token2\n# comment\n\norg3=token3\n").unwrap();
+
+        assert_eq!(
+            tokens,
+            HashMap::from([
+                (String::from("org1"), String::from("token1")),
+                (String::from("org2"), String::from("token2")),
+                (String::from("org3"), String::from("token3")),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_token_entries_without_org() {
+        assert!(AuthConfig::parse_tokens("=token1").is_err());
+        assert!(AuthConfig::parse_tokens("token1").is_err());
+    }
+
+    #[test]
+    fn does_not_print_tokens_in_debug_output() {
+        let config = AuthConfig {
+            tokens: HashMap::from([(String::from("org1"), String::from("secret"))]),
+            ..AuthConfig::default()
+        };
+
+        assert!(!format!("{:?}", config).contains("secret"));
+    }
+
+    #[test]
+    fn loads_defaults_without_file_or_env() {
+        let config = Config::load(None, no_env).unwrap();
+
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.bind_ip, "127.0.0.1");
+        assert_eq!(config.upstream.max_pages, 10);
+        assert_eq!(config.upstream.max_pipeline_pages, 1);
+        assert_eq!(config.upstream.max_concurrent_requests, 4);
+        assert!(config.auth.header_passthrough);
+    }
+
+    #[test]
+    fn loads_settings_from_file() {
+        let path = write_config_file(
+            "loads_settings_from_file.toml",
+            r#"
+            [server]
+            port = 9090
+
+            [upstream]
+            base_url = "https://ci.example.com"
+            retry_backoff_millis = 100
+
+            [cache]
+            ttl_seconds = 30
+
+            [feed]
+            stopped_status = "Failure"
+
+            [auth.tokens]
+            my-org = "my-token"
+            "#,
+        );
+
+        let config = Config::load(Some(&path), no_env).unwrap();
+
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.upstream.base_url, Some(String::from("https://ci.example.com")));
+        assert_eq!(config.upstream.retry_backoff, Duration::from_millis(100));
+        assert_eq!(config.cache.ttl, Duration::from_secs(30));
+        assert_eq!(config.feed.stopped_status, ErrorStatus::Failure);
+        assert_eq!(config.auth.tokens["my-org"], "my-token");
+    }
+
+    #[test]
+    fn env_vars_override_file_settings() {
+        let path = write_config_file("env_vars_override_file_settings.toml", "[server]\nport = 9090\n");
+
+        let config = Config::load(Some(&path), |name| match name {
+            "PORT" => Some(String::from("7070")),
+            "BIND_IP" => Some(String::from("localhost")),
+            "FALLBACK_UNKNOWN_STATUS" => Some(String::from("true")),
+            "FEED_HIDE_STALE_PROMOTIONS" => Some(String::from("true")),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(config.server.port, 7070);
+        assert_eq!(config.server.bind_ip, "localhost");
+        assert!(config.fallback.unknown_status);
+        assert!(config.feed.hide_stale_promotions);
+    }
+
+    #[test]
+    fn rejects_invalid_env_var_values() {
+        let error = Config::load(None, |name| match name {
+            "PORT" => Some(String::from("eighty")),
+            _ => None,
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid value \"eighty\" for PORT: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_settings_in_file() {
+        let path = write_config_file("rejects_unknown_settings_in_file.toml", "[server]\nprot = 9090\n");
+
+        let error = Config::load(Some(&path), no_env).unwrap_err();
+
+        assert!(matches!(error, ConfigError::Parse(_, _)), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_base_url() {
+        let error = Config::load(None, |name| match name {
+            "CI_BASE_URL" => Some(String::from("ci.example.com")),
+            _ => None,
+        })
+        .unwrap_err();
+
+        assert!(matches!(error, ConfigError::Invalid(_)), "{}", error);
+    }
+
+    #[test]
+    fn merges_tokens_from_tokens_file() {
+        let tokens_path = write_config_file("merges_tokens_from_tokens_file.tokens", "org1=file-token\norg2=file-token\n");
+
+        let config = Config::load(None, |name| match name {
+            "SEMAPHORE_TOKENS_FILE" => Some(tokens_path.display().to_string()),
+            "SEMAPHORE_TOKENS" => Some(String::from("org1=env-token")),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(config.auth.tokens["org1"], "env-token");
+        assert_eq!(config.auth.tokens["org2"], "file-token");
+    }
+
+    #[test]
+    fn loads_poller_targets() {
+        let path = write_config_file(
+            "loads_poller_targets.toml",
+            r#"
+            [poller]
+            targets = ["my-org/my-project"]
+
+            [auth.tokens]
+            my-org = "my-token"
+            "#,
+        );
+
+        let config = Config::load(Some(&path), |name| match name {
+            "POLLER_TARGETS" => Some(String::from("my-org/project-1, my-org/project-2")),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            config.poller.targets,
+            vec![
+                PollTarget {
+                    org: String::from("my-org"),
+                    project: String::from("project-1"),
+                },
+                PollTarget {
+                    org: String::from("my-org"),
+                    project: String::from("project-2"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_poller_targets_without_token() {
+        let error = Config::load(None, |name| match name {
+            "POLLER_TARGETS" => Some(String::from("my-org/my-project")),
+            _ => None,
+        })
+        .unwrap_err();
+
+        assert!(error.to_string().contains("no token configured"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_poller_targets() {
+        assert!("my-org".parse::<PollTarget>().is_err());
+        assert!("my-org/".parse::<PollTarget>().is_err());
+        assert!("my-org/a/b".parse::<PollTarget>().is_err());
+    }
+
+    #[test]
+    fn loads_project_patterns() {
+        let path = write_config_file(
+            "loads_project_patterns.toml",
+            r#"
+            [projects."my-org/my-project"]
+            include = ["build", "deploy-*"]
+            exclude = ["/^deploy-(dev|test)$/"]
+            "#,
+        );
+
+        let config = Config::load(Some(&path), no_env).unwrap();
+        let project = &config.projects["my-org/my-project"];
+
+        assert!(project.include[1].is_match("deploy-staging"));
+        assert!(!project.include[1].is_match("predeploy-staging"));
+        assert!(project.exclude[0].is_match("deploy-dev"));
+    }
+
+    #[test]
+    fn rejects_invalid_project_patterns() {
+        let path = write_config_file(
+            "rejects_invalid_project_patterns.toml",
+            "[projects.\"my-org/my-project\"]\nexclude = [\"/(/\"]\n",
+        );
+
+        assert!(matches!(Config::load(Some(&path), no_env), Err(ConfigError::Parse(..))));
+    }
+
+    #[test]
+    fn parses_globs_and_regular_expressions() {
+        let patterns = NamePattern::parse_list("build, deploy-?, /^nightly-.*$/").unwrap();
+
+        assert!(patterns[0].is_match("build"));
+        assert!(!patterns[0].is_match("build [main]"));
+        assert!(patterns[1].is_match("deploy-1"));
+        assert!(!patterns[1].is_match("deploy-12"));
+        assert!(patterns[2].is_match("nightly-perf"));
+        assert!(NamePattern::parse_list("/[/").is_err());
+    }
+
+    #[test]
+    fn renders_name_templates() {
+        let template: NameTemplate = "{project}:{branch}:{pipeline} ({org})".parse().unwrap();
+
+        let name = template.render(|placeholder| match placeholder {
+            NamePlaceholder::Org => String::from("my-org"),
+            NamePlaceholder::Project => String::from("my-project"),
+            NamePlaceholder::Pipeline => String::from("build"),
+            NamePlaceholder::Branch => String::from("main"),
+            NamePlaceholder::Name => String::from("build [main]"),
+        });
+
+        assert_eq!(name, "my-project:main:build (my-org)");
+    }
+
+    #[test]
+    fn parses_label_sources_and_templates() {
+        let sha: LabelTemplate = "sha".parse().unwrap();
+        let template: LabelTemplate = "{branch}@{sha}".parse().unwrap();
+        let value = |placeholder| match placeholder {
+            LabelPlaceholder::Sha => Some(String::from("4f5e6d7")),
+            LabelPlaceholder::Branch => Some(String::from("main")),
+            _ => None,
+        };
+
+        assert_eq!(sha.try_render(value), Some(String::from("4f5e6d7")));
+        assert_eq!(template.try_render(value), Some(String::from("main@4f5e6d7")));
+        assert_eq!("{tag}".parse::<LabelTemplate>().unwrap().try_render(value), None);
+        assert!("{author}".parse::<LabelTemplate>().is_err());
+    }
+
+    #[test]
+    fn reads_name_templates_without_braces_as_text() {
+        let template: NameTemplate = "pipeline".parse().unwrap();
+
+        assert_eq!(template.render(|_| String::from("build")), "pipeline");
+    }
+
+    #[test]
+    fn rejects_invalid_name_templates() {
+        assert!("{project}/{workflow}".parse::<NameTemplate>().is_err());
+        assert!("{project".parse::<NameTemplate>().is_err());
+
+        let error = Config::load(None, |name| match name {
+            "FEED_NAME_TEMPLATE" => Some(String::from("{repo}")),
+            _ => None,
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid value \"{repo}\" for FEED_NAME_TEMPLATE: Invalid template {repo}: unknown placeholder {repo}"
+        );
+    }
+
+    #[test]
+    fn redacts_tokens_when_printing_config() {
+        let mut config = Config::default();
+        config.auth.tokens.insert(String::from("my-org"), String::from("secret"));
+        config.health.token = Some(String::from("health-secret"));
+        config.webhook.secret = Some(String::from("webhook-secret"));
+
+        let printed = config.to_redacted_toml();
+
+        assert!(printed.contains("my-org = \"<redacted>\""), "{}", printed);
+        assert!(printed.contains("token = \"<redacted>\""), "{}", printed);
+        assert!(printed.contains("secret = \"<redacted>\""), "{}", printed);
+        assert!(!printed.contains("\"secret\""), "{}", printed);
+        assert!(!printed.contains("health-secret"), "{}", printed);
+        assert!(!printed.contains("webhook-secret"), "{}", printed);
+        toml::from_str::<Config>(&printed).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use crate::cache::CacheKey;
+use crate::cctray::{self, CCTrayProjectInfo, FeedOptions};
+use crate::semaphoreci::{self, PipelineDetails, State};
+use crate::AppState;
+use futures::stream::{self, StreamExt};
+use log::warn;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::iter;
+use std::sync::Mutex;
+
+/// Details of done pipelines never change, so without a cap they would pile up for the lifetime
+/// of the server. Only broken builds and the pipelines split into blocks are looked up, which
+/// keeps the ones still shown in feeds far below this.
+const MAX_PIPELINE_DETAILS: usize = 1000;
+
+#[derive(Default)]
+struct Entries {
+    details: HashMap<CacheKey, PipelineDetails>,
+    keys: VecDeque<CacheKey>,
+}
+
+/*
+ * The details of done pipelines, which cctray messages and per block projects are built from.
+ * They don't change once the pipeline is done, so they are fetched once, rather than on every feed
+ * request. Details are kept per token, like cached responses.
+ */
+#[derive(Default)]
+pub struct PipelineDetailsCache {
+    entries: Mutex<Entries>,
+}
+
+impl PipelineDetailsCache {
+    fn get(&self, key: &CacheKey) -> Option<PipelineDetails> {
+        self.entries.lock().unwrap().details.get(key).cloned()
+    }
+
+    fn insert(&self, key: CacheKey, details: PipelineDetails) {
+        if details.pipeline.state != Some(State::DONE) {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.details.insert(key.clone(), details).is_none() {
+            entries.keys.push_back(key);
+        }
+        while entries.keys.len() > MAX_PIPELINE_DETAILS {
+            if let Some(oldest) = entries.keys.pop_front() {
+                entries.details.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn get_key(base_url: &str, auth_token: &str, cctray_project: &CCTrayProjectInfo) -> Option<CacheKey> {
+    cctray_project
+        .last_build_pipeline_id
+        .as_deref()
+        .filter(|_| cctray_project.is_broken())
+        .map(|ppl_id| CacheKey::new(base_url, auth_token, Some(ppl_id)))
+}
+
+/// Fetches the details of the broken builds which aren't known yet, a few at a time. Failures are
+/// only logged, as messages are optional.
+pub async fn fetch_details(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    cctray_projects: &[CCTrayProjectInfo],
+) {
+    let missing = cctray_projects
+        .iter()
+        .filter(|p| get_key(base_url, auth_token, p).is_some_and(|key| data.pipeline_details.get(&key).is_none()))
+        .filter_map(|p| p.last_build_pipeline_id.as_deref());
+
+    stream::iter(missing.map(|ppl_id| async move {
+        match semaphoreci::get_pipeline_details(base_url, ppl_id, auth_token, &data.client).await {
+            Ok(details) => data
+                .pipeline_details
+                .insert(CacheKey::new(base_url, auth_token, Some(ppl_id)), details),
+            Err(e) => warn!("Failed to get the details of pipeline {}: {}", ppl_id, e),
+        }
+    }))
+    .buffer_unordered(data.max_concurrent_requests)
+    .collect::<()>()
+    .await;
+}
+
+/// Adds messages to the broken builds whose details are known.
+pub fn add_known_messages(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    cctray_projects: &mut [CCTrayProjectInfo],
+) {
+    for cctray_project in cctray_projects {
+        let details = get_key(base_url, auth_token, cctray_project).and_then(|key| data.pipeline_details.get(&key));
+
+        if let Some(details) = details {
+            cctray::add_messages(cctray_project, &details);
+        }
+    }
+}
+
+pub async fn add_messages(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    cctray_projects: &mut [CCTrayProjectInfo],
+) {
+    fetch_details(data, base_url, auth_token, cctray_projects).await;
+    add_known_messages(data, base_url, auth_token, cctray_projects);
+}
+
+/*
+ * The details of pipelines, by id. Those of running pipelines are still changing, so they are
+ * cached like other responses, for `cache.ttl_seconds`, until the pipeline is done. Pipelines
+ * whose details can't be fetched are left out. Like the projects of an organisation feed, at most
+ * `upstream.max_concurrent_requests` are fetched at the same time.
+ */
+pub async fn get_details<'a>(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    ppl_ids: impl IntoIterator<Item = &'a str>,
+) -> HashMap<String, PipelineDetails> {
+    let ppl_ids: HashSet<&str> = ppl_ids.into_iter().collect();
+
+    stream::iter(ppl_ids.into_iter().map(|ppl_id| async move {
+        let key = CacheKey::new(base_url, auth_token, Some(ppl_id));
+        if let Some(details) = data.pipeline_details.get(&key) {
+            return Some((ppl_id.to_string(), details));
+        }
+
+        match get_running_pipeline_details(data, key.clone(), base_url, ppl_id, auth_token).await {
+            Ok(details) => {
+                data.pipeline_details.insert(key, details.clone());
+                Some((ppl_id.to_string(), details))
+            }
+            Err(e) => {
+                warn!("Failed to get the details of pipeline {}: {}", ppl_id, e);
+                None
+            }
+        }
+    }))
+    .buffer_unordered(data.max_concurrent_requests)
+    .filter_map(|details| async move { details })
+    .collect()
+    .await
+}
+
+async fn get_running_pipeline_details(
+    data: &AppState,
+    key: CacheKey,
+    base_url: &str,
+    ppl_id: &str,
+    auth_token: &str,
+) -> Result<PipelineDetails, semaphoreci::Error> {
+    let client = data.client.clone();
+    let base_url = base_url.to_string();
+    let ppl_id = ppl_id.to_string();
+    let auth_token = auth_token.to_string();
+
+    data.running_pipeline_details_cache
+        .get_or_fetch(key, move || {
+            let (client, base_url, ppl_id, auth_token) =
+                (client.clone(), base_url.clone(), ppl_id.clone(), auth_token.clone());
+            async move { semaphoreci::get_pipeline_details(&base_url, &ppl_id, &auth_token, &client).await }
+        })
+        .await
+}
+
+/// Splits cctray projects into one project per block, or per job, of their latest and last
+/// completed pipelines.
+pub async fn split_by_task(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    cctray_projects: Vec<CCTrayProjectInfo>,
+    jobs: bool,
+    options: &FeedOptions,
+) -> Vec<CCTrayProjectInfo> {
+    let ppl_ids = cctray_projects
+        .iter()
+        .flat_map(|p| iter::once(p.pipeline_id.as_str()).chain(p.last_build_pipeline_id.as_deref()));
+    let details = get_details(data, base_url, auth_token, ppl_ids).await;
+
+    cctray::split_by_task(cctray_projects, &details, jobs, options)
+}
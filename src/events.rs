@@ -0,0 +1,264 @@
+use crate::cctray::{self, Activity, BuildStatus, CCTrayProjectInfo};
+use crate::fallback::FeedKey;
+use crate::{get_polled_feed, get_project_feed, AppState, FeedError, FeedQuery, ProjectInfo};
+use actix_web::web::{self, Bytes};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+/// A change of the activity or build status of a cctray project. Previous values are missing
+/// for projects which weren't in the feed before.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusChange<'a> {
+    #[serde(flatten)]
+    pub project: &'a CCTrayProjectInfo,
+    pub previous_activity: Option<Activity>,
+    pub previous_last_build_status: Option<BuildStatus>,
+}
+
+pub fn status_changes<'a>(
+    previous: &[CCTrayProjectInfo],
+    current: &'a [CCTrayProjectInfo],
+) -> Vec<StatusChange<'a>> {
+    current
+        .iter()
+        .filter_map(|project| {
+            let previous = previous.iter().find(|p| p.name == project.name);
+
+            match previous {
+                Some(p) if p.activity == project.activity && p.last_build_status == project.last_build_status => {
+                    None
+                }
+                _ => Some(StatusChange {
+                    project,
+                    previous_activity: previous.map(|p| p.activity.clone()),
+                    previous_last_build_status: previous.map(|p| p.last_build_status),
+                }),
+            }
+        })
+        .collect()
+}
+
+fn event(name: &str, data: &str) -> Bytes {
+    Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+}
+
+/// How many messages a client may fall behind before it misses some.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// A feed being watched for changes, and the clients listening to them.
+struct Watcher {
+    sender: broadcast::Sender<Bytes>,
+    /// The feed the next changes are found against, sent first to clients which start listening.
+    feed: Mutex<Vec<CCTrayProjectInfo>>,
+}
+
+/// The messages of a watcher, following the feed they are changes of.
+struct Subscription {
+    feed: Vec<CCTrayProjectInfo>,
+    receiver: broadcast::Receiver<Bytes>,
+}
+
+impl Watcher {
+    fn subscribe(&self) -> Subscription {
+        let feed = self.feed.lock().unwrap();
+
+        Subscription {
+            feed: feed.clone(),
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+/*
+ * The feeds clients listen to, by path, query and token. Each feed is watched by a single task,
+ * whatever the number of clients listening to it, so that SemaphoreCI is called once per interval
+ * for each feed rather than once per client. The task stops once nobody listens anymore.
+ */
+#[derive(Default)]
+pub struct Watchers {
+    watchers: Mutex<HashMap<FeedKey, Arc<Watcher>>>,
+}
+
+impl Watchers {
+    fn subscribe(&self, key: &FeedKey) -> Option<Subscription> {
+        self.watchers.lock().unwrap().get(key).map(|watcher| watcher.subscribe())
+    }
+
+    /// Starts watching a feed, unless another client did in the meantime. The new watcher is
+    /// returned, to be run by the caller.
+    fn start(&self, key: FeedKey, feed: Vec<CCTrayProjectInfo>) -> (Subscription, Option<Arc<Watcher>>) {
+        let mut watchers = self.watchers.lock().unwrap();
+
+        if let Some(watcher) = watchers.get(&key) {
+            return (watcher.subscribe(), None);
+        }
+
+        let watcher = Arc::new(Watcher {
+            sender: broadcast::channel(CHANNEL_CAPACITY).0,
+            feed: Mutex::new(feed),
+        });
+        watchers.insert(key, watcher.clone());
+
+        (watcher.subscribe(), Some(watcher))
+    }
+
+    /// Stops watching a feed nobody listens to, telling whether it was stopped.
+    fn stop_unused(&self, key: &FeedKey) -> bool {
+        let mut watchers = self.watchers.lock().unwrap();
+
+        let unused = watchers.get(key).is_some_and(|watcher| watcher.sender.receiver_count() == 0);
+        if unused {
+            watchers.remove(key);
+        }
+
+        unused
+    }
+}
+
+/*
+ * The feed of a project being listened to, checked for changes on an interval. The feed is read
+ * the same way as for the cctray routes, ie. from the poller, the cache, or SemaphoreCI.
+ */
+pub struct Listener {
+    pub data: web::Data<AppState>,
+    pub base_url: String,
+    pub auth_token: String,
+    pub info: ProjectInfo,
+    pub query: FeedQuery,
+}
+
+impl Listener {
+    pub async fn get_feed(&self) -> Result<Vec<CCTrayProjectInfo>, FeedError> {
+        if let Some((cctray_projects, _)) = get_polled_feed(&self.data, &self.info, &self.query) {
+            return Ok(cctray_projects);
+        }
+
+        get_project_feed(&self.data, &self.base_url, &self.auth_token, &self.info, &self.query).await
+    }
+
+    /*
+     * Streams a `projects` event with the current feed, followed by a `change` event for each
+     * project whose activity or build status changes. A comment is sent when nothing changed, so
+     * that disconnected clients are noticed. Failures to read the feed are sent as `error`
+     * events, and don't end the stream. Clients of a feed which is already watched join its
+     * watcher, others start one, failing when the feed can't be read.
+     */
+    pub async fn subscribe(
+        self,
+        key: FeedKey,
+    ) -> Result<impl Stream<Item = Result<Bytes, actix_web::Error>>, FeedError> {
+        let subscription = match self.data.event_watchers.subscribe(&key) {
+            Some(subscription) => subscription,
+            None => {
+                let feed = self.get_feed().await?;
+                let (subscription, watcher) = self.data.event_watchers.start(key.clone(), feed);
+                if let Some(watcher) = watcher {
+                    actix_web::rt::spawn(self.watch(key, watcher));
+                }
+                subscription
+            }
+        };
+
+        let snapshot = event("projects", &cctray::serialize_json(subscription.feed));
+        let messages = stream::unfold(subscription.receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(message) => return Some((Ok(message), receiver)),
+                    /* Changes missed by a slow client are skipped, the next ones are still sent */
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(stream::once(async move { Ok(snapshot) }).chain(messages))
+    }
+
+    async fn watch(self, key: FeedKey, watcher: Arc<Watcher>) {
+        loop {
+            actix_web::rt::time::sleep(self.data.events.interval).await;
+
+            if self.data.event_watchers.stop_unused(&key) {
+                return;
+            }
+
+            let current = self.get_feed().await;
+
+            /* Sent while holding the feed, so that clients joining meanwhile get consistent changes */
+            let mut feed = watcher.feed.lock().unwrap();
+            let message = match current {
+                Ok(current) => {
+                    let changes = status_changes(&feed, &current);
+                    let message = if changes.is_empty() {
+                        Bytes::from_static(b": no changes\n\n")
+                    } else {
+                        changes
+                            .iter()
+                            .map(|change| {
+                                event("change", &serde_json::to_string(change).expect("Changes can be serialised"))
+                            })
+                            .collect::<Vec<_>>()
+                            .concat()
+                            .into()
+                    };
+                    *feed = current;
+                    message
+                }
+                Err(e) => {
+                    let message = json!({ "message": actix_web::Error::from(e).to_string() });
+                    event("error", &message.to_string())
+                }
+            };
+            let _ = watcher.sender.send(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, activity: Activity, last_build_status: BuildStatus) -> CCTrayProjectInfo {
+        CCTrayProjectInfo::with_status(name, activity, last_build_status)
+    }
+
+    #[test]
+    fn reports_changed_and_new_projects_only() {
+        let previous = vec![
+            project("build", Activity::Sleeping, BuildStatus::Success),
+            project("deploy", Activity::Sleeping, BuildStatus::Success),
+        ];
+        let current = vec![
+            project("build", Activity::Building, BuildStatus::Success),
+            project("deploy", Activity::Sleeping, BuildStatus::Success),
+            project("lint", Activity::Sleeping, BuildStatus::Failure),
+        ];
+
+        let changes = status_changes(&previous, &current);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].project.name, "build");
+        assert_eq!(changes[0].previous_activity, Some(Activity::Sleeping));
+        assert_eq!(changes[1].project.name, "lint");
+        assert_eq!(changes[1].previous_last_build_status, None);
+    }
+
+    #[test]
+    fn serialises_change_with_previous_values() {
+        let previous = vec![project("build", Activity::Sleeping, BuildStatus::Success)];
+        let current = vec![project("build", Activity::Sleeping, BuildStatus::Failure)];
+
+        let json = serde_json::to_value(&status_changes(&previous, &current)[0]).unwrap();
+
+        assert_eq!(json["name"], "build");
+        assert_eq!(json["lastBuildStatus"], "Failure");
+        assert_eq!(json["previousLastBuildStatus"], "Success");
+        assert_eq!(json["previousActivity"], "Sleeping");
+    }
+}
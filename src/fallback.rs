@@ -0,0 +1,168 @@
+use crate::cache::hash_token;
+use crate::cctray::{BuildStatus, CCTrayProjectInfo};
+use crate::config::FallbackConfig;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// One last good feed is kept per path, query and token, so this bounds the memory used by
+/// clients requesting many distinct feeds. It is well above the feeds a team's monitors poll.
+const MAX_FEEDS: usize = 1000;
+
+/*
+ * Identifies a feed, by its path and query, and the token it was fetched with, so that a feed is
+ * never served to a client using another token.
+ */
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct FeedKey {
+    path_and_query: String,
+    token_hash: u64,
+}
+
+impl FeedKey {
+    pub fn new(path: &str, query: &str, auth_token: &str) -> Self {
+        FeedKey {
+            path_and_query: format!("{}?{}", path, query),
+            token_hash: hash_token(auth_token),
+        }
+    }
+}
+
+struct Entry {
+    projects: Vec<CCTrayProjectInfo>,
+    built_at: Instant,
+}
+
+/*
+ * The last feeds successfully built from SemaphoreCI responses, served when it is unavailable so
+ * that a blip doesn't turn every monitor red. Feeds older than `max_staleness` are dropped, as are
+ * the oldest ones beyond `MAX_FEEDS`.
+ */
+pub struct LastGoodFeeds {
+    max_staleness: Duration,
+    max_feeds: usize,
+    unknown_status: bool,
+    feeds: Mutex<HashMap<FeedKey, Entry>>,
+}
+
+impl LastGoodFeeds {
+    pub fn new(config: &FallbackConfig) -> Self {
+        LastGoodFeeds {
+            max_staleness: config.max_staleness,
+            max_feeds: MAX_FEEDS,
+            unknown_status: config.unknown_status,
+            feeds: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The last good feed, and its age, unless it is older than `max_staleness`.
+    pub fn get(&self, key: &FeedKey) -> Option<(Vec<CCTrayProjectInfo>, Duration)> {
+        let feeds = self.feeds.lock().unwrap();
+        let entry = feeds.get(key)?;
+        let age = entry.built_at.elapsed();
+
+        if age > self.max_staleness {
+            return None;
+        }
+
+        let mut projects = entry.projects.clone();
+        if self.unknown_status {
+            projects
+                .iter_mut()
+                .for_each(|project| project.last_build_status = BuildStatus::Unknown);
+        }
+
+        Some((projects, age))
+    }
+
+    pub fn insert(&self, key: FeedKey, projects: Vec<CCTrayProjectInfo>) {
+        if self.max_staleness.is_zero() {
+            return;
+        }
+
+        let mut feeds = self.feeds.lock().unwrap();
+
+        feeds.retain(|_, entry| entry.built_at.elapsed() <= self.max_staleness);
+        feeds.insert(
+            key,
+            Entry {
+                projects,
+                built_at: Instant::now(),
+            },
+        );
+        while feeds.len() > self.max_feeds {
+            let oldest = feeds.iter().min_by_key(|(_, entry)| entry.built_at).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                feeds.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cctray::Activity;
+
+    fn project() -> CCTrayProjectInfo {
+        CCTrayProjectInfo::with_status("build", Activity::Sleeping, BuildStatus::Success)
+    }
+
+    fn fallback_config(max_staleness: Duration, unknown_status: bool) -> FallbackConfig {
+        FallbackConfig {
+            max_staleness,
+            unknown_status,
+        }
+    }
+
+    #[test]
+    fn serves_feeds_to_the_same_token_only() {
+        let feeds = LastGoodFeeds::new(&fallback_config(Duration::from_secs(60), false));
+        feeds.insert(FeedKey::new("/org/project/cctray", "", "token"), vec![project()]);
+
+        let (projects, _) = feeds.get(&FeedKey::new("/org/project/cctray", "", "token")).unwrap();
+
+        assert_eq!(projects[0].last_build_status, BuildStatus::Success);
+        assert!(feeds.get(&FeedKey::new("/org/project/cctray", "", "other-token")).is_none());
+    }
+
+    #[test]
+    fn marks_projects_unknown_when_configured() {
+        let feeds = LastGoodFeeds::new(&fallback_config(Duration::from_secs(60), true));
+        let key = FeedKey::new("/org/project/cctray", "", "token");
+        feeds.insert(key.clone(), vec![project()]);
+
+        let (projects, _) = feeds.get(&key).unwrap();
+
+        assert_eq!(projects[0].last_build_status, BuildStatus::Unknown);
+    }
+
+    #[test]
+    fn does_not_serve_feeds_older_than_max_staleness() {
+        let feeds = LastGoodFeeds::new(&fallback_config(Duration::from_millis(10), false));
+        let key = FeedKey::new("/org/project/cctray", "", "token");
+        feeds.insert(key.clone(), vec![project()]);
+
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(feeds.get(&key).is_none());
+    }
+
+    #[test]
+    fn drops_the_oldest_feeds_beyond_the_maximum() {
+        let feeds = LastGoodFeeds {
+            max_feeds: 2,
+            ..LastGoodFeeds::new(&fallback_config(Duration::from_secs(60), false))
+        };
+        let key = |query: &str| FeedKey::new("/org/project/cctray", query, "token");
+
+        for query in ["a=1", "a=2", "a=3"] {
+            feeds.insert(key(query), vec![project()]);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(feeds.get(&key("a=1")).is_none());
+        assert!(feeds.get(&key("a=2")).is_some());
+        assert!(feeds.get(&key("a=3")).is_some());
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::HealthConfig;
+use crate::semaphoreci::{self, Client};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Error,
+    Skipped,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub status: Status,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// A report is ok unless one of its checks failed. Skipped checks don't make it fail.
+    pub fn new(checks: Vec<Check>) -> Self {
+        let status = if checks.iter().any(|check| check.status == Status::Error) {
+            Status::Error
+        } else {
+            Status::Ok
+        };
+
+        Report { status, checks }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == Status::Ok
+    }
+}
+
+/// The server process is up, and able to handle requests.
+pub fn check_process() -> Check {
+    Check {
+        name: "process",
+        status: Status::Ok,
+        message: None,
+    }
+}
+
+/*
+ * SemaphoreCI can only be checked when its URL is configured, as it's otherwise derived from the
+ * organisation of each request. Without a token, any response other than a server error means
+ * SemaphoreCI can be reached; with one, it must also accept the token.
+ */
+pub async fn check_upstream(base_url: Option<&str>, config: &HealthConfig, client: &Client) -> Check {
+    let name = "upstream";
+
+    let Some(base_url) = base_url else {
+        return Check {
+            name,
+            status: Status::Skipped,
+            message: Some(String::from("No upstream.base_url configured")),
+        };
+    };
+
+    let token = config.token.as_deref();
+    let (status, message) = match semaphoreci::get_status(base_url, token, config.timeout, client).await {
+        Ok(code) if code.is_success() || (token.is_none() && !code.is_server_error()) => {
+            (Status::Ok, format!("{} responded with {}", base_url, code))
+        }
+        Ok(code) => (Status::Error, format!("{} responded with {}", base_url, code)),
+        Err(e) => (Status::Error, format!("{} is unreachable: {}", base_url, e)),
+    };
+
+    Check {
+        name,
+        status,
+        message: Some(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(status: Status) -> Check {
+        Check {
+            name: "test",
+            status,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn report_fails_when_a_check_fails() {
+        let report = Report::new(vec![check(Status::Ok), check(Status::Error)]);
+
+        assert_eq!(report.status, Status::Error);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn report_is_ok_when_checks_are_skipped() {
+        let report = Report::new(vec![check(Status::Ok), check(Status::Skipped)]);
+
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn serialises_report_as_json() {
+        let report = Report::new(vec![check_process()]);
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"status":"ok","checks":[{"name":"process","status":"ok"}]}"#
+        );
+    }
+}
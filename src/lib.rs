@@ -1,11 +1,56 @@
+mod cache;
 mod cctray;
+mod circuit_breaker;
+pub mod config;
+mod details;
+mod events;
+mod fallback;
+mod health;
+mod metrics;
+mod poller;
 mod semaphoreci;
+mod webhook;
 
-use actix_web::http::header::{ContentType, HeaderMap};
+use crate::cache::{CacheKey, ResponseCache};
+use crate::config::{
+    AuthConfig, Config, EventsConfig, HealthConfig, LabelTemplate, NamePattern, NameTemplate,
+    PollerConfig, ProjectConfig, WebhookConfig,
+};
+use crate::fallback::{FeedKey, LastGoodFeeds};
+use crate::health::Report;
+use crate::cctray::{BuildStatus, CCTrayProjectInfo, FeedOptions};
+use crate::details::PipelineDetailsCache;
+use crate::metrics::Metrics;
+use crate::poller::PolledFeeds;
+use crate::semaphoreci::{Pipeline, PipelineDetails, Project, Workflow};
+use crate::webhook::WebhookPipelines;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{
+    Accept, CacheControl, CacheDirective, ContentType, Header, HeaderMap, HeaderValue, AGE, VARY,
+    WARNING,
+};
+use actix_web::middleware::{from_fn, Next};
 use actix_web::web::Path;
-use actix_web::{error, route, routes, web, HttpRequest, HttpResponse, Responder};
-use serde::Deserialize;
+use actix_web::{
+    error, get, mime, post, route, routes, web, HttpRequest, HttpResponse, Responder,
+};
+use futures::future::try_join;
+use futures::stream::{self, StreamExt};
+use itertools::Itertools;
+use log::warn;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::convert::Into;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Deserialize)]
+struct OrgInfo {
+    org: String,
+}
 
 #[derive(Deserialize)]
 struct ProjectInfo {
@@ -13,9 +58,180 @@ struct ProjectInfo {
     project: String,
 }
 
-struct AppState {
-    client: reqwest::Client,
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum GroupBy {
+    /// One cctray project per pipeline name (eg. "build", "deploy").
+    #[default]
+    Pipeline,
+    /// One cctray project per pipeline name and branch (eg. "build [main]").
+    PipelineAndBranch,
+    /// One cctray project per branch, following the workflows run on that branch.
+    Workflow,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Granularity {
+    /// One cctray project per pipeline, or whatever the pipelines are grouped by.
+    #[default]
+    Pipeline,
+    /// One cctray project per block of the pipelines (eg. "build :: Unit tests").
+    Block,
+    /// One cctray project per job of the pipeline blocks (eg. "build :: Unit tests :: rspec").
+    Job,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Promotions {
+    /// Every promotion target which was ever triggered.
+    All,
+    /// Only the promotions triggered from the latest run of their parent pipeline, hiding the
+    /// stale ones until the latest run is promoted too.
+    Current,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Format {
+    Xml,
+    Json,
+}
+
+#[derive(Deserialize, Default)]
+struct FeedQuery {
+    #[serde(default)]
+    group_by: GroupBy,
+    #[serde(default)]
+    granularity: Granularity,
+    /// Only includes the pipelines run on this branch.
+    branch: Option<String>,
+    /// Overrides the format negotiated with the `Accept` header.
+    format: Option<Format>,
+    /// Comma separated patterns of the cctray projects to keep, instead of the configured ones.
+    #[serde(default, deserialize_with = "deserialize_name_patterns")]
+    include: Option<Vec<NamePattern>>,
+    /// Comma separated patterns of the cctray projects to leave out, instead of the configured
+    /// ones.
+    #[serde(default, deserialize_with = "deserialize_name_patterns")]
+    exclude: Option<Vec<NamePattern>>,
+    /// Template the cctray projects are named with, instead of the configured one.
+    name: Option<NameTemplate>,
+    /// Source of the last build labels, instead of the configured one.
+    label: Option<LabelTemplate>,
+    /// Which promotions are listed, instead of the configured ones.
+    promotions: Option<Promotions>,
+}
+
+fn deserialize_name_patterns<'de, D>(deserializer: D) -> Result<Option<Vec<NamePattern>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let patterns = String::deserialize(deserializer)?;
+
+    NamePattern::parse_list(&patterns).map(Some).map_err(D::Error::custom)
+}
+
+impl FeedQuery {
+    /*
+     * The query in a canonical form, so that parameters given in another order, or unknown ones,
+     * don't make feeds distinct. The format is left out, as it only changes how the feed is
+     * serialised.
+     */
+    fn normalized(&self) -> String {
+        let patterns = |patterns: &Option<Vec<NamePattern>>| {
+            patterns.as_ref().map(|patterns| patterns.iter().map(NamePattern::as_str).join(","))
+        };
+
+        format!(
+            "group_by={:?}&granularity={:?}&branch={:?}&include={:?}&exclude={:?}&name={:?}&label={:?}&promotions={:?}",
+            self.group_by,
+            self.granularity,
+            self.branch,
+            patterns(&self.include),
+            patterns(&self.exclude),
+            self.name.as_ref().map(NameTemplate::as_str),
+            self.label.as_ref().map(LabelTemplate::as_str),
+            self.promotions,
+        )
+    }
+
+    /// Whether the feed is the default one of its project, as built by the poller. The format
+    /// doesn't matter, as it only changes how the feed is serialised, nor do the name patterns,
+    /// the templates and the promotions, which are applied to the polled feed.
+    fn is_default_feed(&self) -> bool {
+        self.group_by == GroupBy::Pipeline && self.granularity == Granularity::Pipeline && self.branch.is_none()
+    }
+}
+
+pub struct AppState {
+    client: semaphoreci::Client,
     base_url: Option<String>,
+    feed_options: FeedOptions,
+    auth: AuthConfig,
+    health: HealthConfig,
+    metrics: Arc<Metrics>,
+    projects_cache: Arc<ResponseCache<Vec<Project>>>,
+    pipelines_cache: Arc<ResponseCache<Vec<Pipeline>>>,
+    workflows_cache: Arc<ResponseCache<Vec<Workflow>>>,
+    pipeline_details: PipelineDetailsCache,
+    running_pipeline_details_cache: Arc<ResponseCache<PipelineDetails>>,
+    last_good_feeds: LastGoodFeeds,
+    poller: PollerConfig,
+    polled_feeds: PolledFeeds,
+    webhook: WebhookConfig,
+    webhook_pipelines: WebhookPipelines,
+    events: EventsConfig,
+    event_watchers: events::Watchers,
+    projects: HashMap<String, ProjectConfig>,
+    max_concurrent_requests: usize,
+}
+
+impl AppState {
+    pub fn new(config: &Config) -> Self {
+        let cache = &config.cache;
+        let metrics = Arc::new(Metrics::new());
+
+        AppState {
+            client: semaphoreci::Client::new(&config.upstream, metrics.clone()),
+            base_url: config.upstream.base_url.clone(),
+            feed_options: FeedOptions::from(&config.feed),
+            auth: config.auth.clone(),
+            health: config.health.clone(),
+            projects_cache: Arc::new(ResponseCache::new(
+                cache.ttl,
+                cache.stale_while_revalidate,
+                metrics.cache_counters("projects"),
+            )),
+            pipelines_cache: Arc::new(ResponseCache::new(
+                cache.ttl,
+                cache.stale_while_revalidate,
+                metrics.cache_counters("pipelines"),
+            )),
+            workflows_cache: Arc::new(ResponseCache::new(
+                cache.ttl,
+                cache.stale_while_revalidate,
+                metrics.cache_counters("workflows"),
+            )),
+            pipeline_details: PipelineDetailsCache::default(),
+            running_pipeline_details_cache: Arc::new(ResponseCache::new(
+                cache.ttl,
+                cache.stale_while_revalidate,
+                metrics.cache_counters("pipeline_details"),
+            )),
+            last_good_feeds: LastGoodFeeds::new(&config.fallback),
+            poller: config.poller.clone(),
+            polled_feeds: PolledFeeds::default(),
+            webhook: config.webhook.clone(),
+            webhook_pipelines: WebhookPipelines::default(),
+            events: config.events.clone(),
+            event_watchers: events::Watchers::default(),
+            projects: config.projects.clone(),
+            max_concurrent_requests: config.upstream.max_concurrent_requests,
+            metrics,
+        }
+    }
 }
 
 #[route("/", method = "GET", method = "HEAD")]
@@ -23,6 +239,64 @@ async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello world!")
 }
 
+#[route("/healthz", method = "GET", method = "HEAD")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(Report::new(vec![health::check_process()]))
+}
+
+#[route("/ready", method = "GET", method = "HEAD")]
+async fn ready(data: web::Data<AppState>) -> impl Responder {
+    let report = Report::new(vec![
+        health::check_process(),
+        health::check_upstream(data.base_url.as_deref(), &data.health, &data.client).await,
+    ]);
+
+    if report.is_ok() {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/*
+ * Receives the notifications SemaphoreCI sends when a pipeline is done, so that feeds reflect it
+ * without waiting for the cache to expire or the next poll. Notifications must be signed with the
+ * configured secret, as they change what every client of the project sees.
+ */
+#[post("/webhooks/semaphoreci")]
+async fn semaphoreci_webhook(
+    req: HttpRequest,
+    body: web::Bytes,
+    data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let secret = data
+        .webhook
+        .secret
+        .as_ref()
+        .ok_or_else(|| error::ErrorNotFound("Webhooks are not enabled"))?;
+
+    let signature = req
+        .headers()
+        .get(webhook::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| error::ErrorUnauthorized("Signature header missing"))?;
+    if !webhook::verify_signature(secret, &body, signature) {
+        return Err(error::ErrorUnauthorized("Signature is invalid"));
+    }
+
+    let payload: webhook::Payload = serde_json::from_slice(&body).map_err(error::ErrorBadRequest)?;
+    data.webhook_pipelines.insert(&payload);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[get("/metrics")]
+async fn prometheus_metrics(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(data.metrics.render())
+}
+
 #[routes]
 #[get("/{org}/{project}/cctray")]
 #[head("/{org}/{project}/cctray")]
@@ -31,63 +305,590 @@ async fn hello() -> impl Responder {
 async fn cctray_project(
     req: HttpRequest,
     info: Path<ProjectInfo>,
+    query: web::Query<FeedQuery>,
     data: web::Data<AppState>,
 ) -> actix_web::Result<HttpResponse> {
-    let base_url = (&data.base_url)
-        .clone()
-        .unwrap_or(format!("https://{}.semaphoreci.com", info.org));
+    let base_url = get_base_url(&data, &info.org);
+    let auth_token = get_token(req.headers(), &info.org, &data.auth).map_err(error::ErrorUnauthorized)?;
 
-    let auth_token = get_token(req.headers()).map_err(|e| error::ErrorUnauthorized(e))?;
+    if let Some(response) = serve_polled_feed(&req, &info, &query, &data) {
+        return Ok(response);
+    }
 
-    let projects = semaphoreci::get_projects(&base_url, &auth_token, &data.client)
-        .await
-        .map_err(to_actix_error)?;
+    let feed = get_project_feed(&data, &base_url, &auth_token, &info, &query).await;
+
+    serve_feed(&req, &query, &data, &auth_token, feed)
+}
+
+#[routes]
+#[get("/{org}/cctray")]
+#[head("/{org}/cctray")]
+async fn cctray_org(
+    req: HttpRequest,
+    info: Path<OrgInfo>,
+    query: web::Query<FeedQuery>,
+    data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let base_url = get_base_url(&data, &info.org);
+    let auth_token = get_token(req.headers(), &info.org, &data.auth).map_err(error::ErrorUnauthorized)?;
+
+    let feed = get_org_feed(&data, &base_url, &auth_token, &info.org, &query).await;
+
+    serve_feed(&req, &query, &data, &auth_token, feed)
+}
+
+/// Server-Sent Events stream of the changes of the activity and build status of a project's feed.
+#[get("/{org}/{project}/events")]
+async fn project_events(
+    req: HttpRequest,
+    info: Path<ProjectInfo>,
+    query: web::Query<FeedQuery>,
+    data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let base_url = get_base_url(&data, &info.org);
+    let auth_token = get_token(req.headers(), &info.org, &data.auth).map_err(error::ErrorUnauthorized)?;
+
+    let key = FeedKey::new(req.path(), &query.normalized(), &auth_token);
+    let listener = events::Listener {
+        data: data.clone(),
+        base_url,
+        auth_token,
+        info: info.into_inner(),
+        query: query.into_inner(),
+    };
+    let events = listener.subscribe(key).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(mime::TEXT_EVENT_STREAM)
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(events))
+}
+
+#[derive(Debug)]
+enum FeedError {
+    Upstream(semaphoreci::Error),
+    ProjectNotFound(String),
+}
+
+impl From<semaphoreci::Error> for FeedError {
+    fn from(e: semaphoreci::Error) -> Self {
+        FeedError::Upstream(e)
+    }
+}
+
+impl From<FeedError> for actix_web::Error {
+    fn from(e: FeedError) -> Self {
+        match e {
+            FeedError::Upstream(e) => to_actix_error(e),
+            FeedError::ProjectNotFound(project) => {
+                error::ErrorNotFound(format!("Project {} not found", project))
+            }
+        }
+    }
+}
+
+async fn get_project_feed(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    info: &ProjectInfo,
+    query: &FeedQuery,
+) -> Result<Vec<CCTrayProjectInfo>, FeedError> {
+    let projects = get_projects(data, base_url, auth_token).await?;
 
     let project = projects
         .iter()
         .find(|&p| p.metadata.name == info.project || p.metadata.id == info.project)
-        .ok_or_else(|| error::ErrorNotFound(format!("Project {} not found", info.project)))?;
+        .ok_or_else(|| FeedError::ProjectNotFound(info.project.clone()))?;
 
-    let pipelines =
-        semaphoreci::get_pipelines(&base_url, &project.metadata.id, &auth_token, &data.client)
-            .await
-            .map_err(to_actix_error)?;
+    Ok(get_cctray_projects(data, base_url, project, auth_token, &info.org, query).await?)
+}
 
-    let cctray_projects = cctray::to_cctray_project_info(pipelines, &info.org);
+async fn get_org_feed(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+    org: &str,
+    query: &FeedQuery,
+) -> Result<Vec<CCTrayProjectInfo>, FeedError> {
+    let projects = get_projects(data, base_url, auth_token).await?;
 
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::xml())
-        .body(cctray::serialize(cctray_projects)))
+    let feeds: Vec<_> = stream::iter(projects.iter().map(|project| async move {
+        (project, get_cctray_projects(data, base_url, project, auth_token, org, query).await)
+    }))
+    .buffered(data.max_concurrent_requests)
+    .collect()
+    .await;
+
+    /* A project whose pipelines can't be fetched is left out, rather than failing the feed of the
+     * whole organisation, unless none of them could be fetched */
+    let mut first_error = None;
+    let cctray_projects_by_project = feeds
+        .into_iter()
+        .filter_map(|(project, feed)| match feed {
+            Ok(cctray_projects) => {
+                let config = get_project_config(data, org, &project.metadata.name, &project.metadata.id);
+                let project_name = get_name_template(data, config, query)
+                    .is_none()
+                    .then(|| project.metadata.name.clone());
+                Some((project_name, cctray_projects))
+            }
+            Err(e) => {
+                warn!("Leaving project {} out of the feed of {}: {}", project.metadata.name, org, e);
+                first_error.get_or_insert(e);
+                None
+            }
+        })
+        .collect_vec();
+
+    match first_error {
+        Some(e) if cctray_projects_by_project.is_empty() => Err(e.into()),
+        _ => Ok(cctray::merge_project_feeds(cctray_projects_by_project)),
+    }
+}
+
+/*
+ * Successful feeds are kept, so that the last good one can be served when SemaphoreCI is
+ * unavailable, rather than an error. It is marked as stale with a `Warning` header. Requests
+ * rejected by SemaphoreCI, eg. because of an invalid token, are never served a fallback feed.
+ */
+fn serve_feed(
+    req: &HttpRequest,
+    query: &FeedQuery,
+    data: &AppState,
+    auth_token: &str,
+    feed: Result<Vec<CCTrayProjectInfo>, FeedError>,
+) -> actix_web::Result<HttpResponse> {
+    let key = FeedKey::new(req.path(), &query.normalized(), auth_token);
+
+    match feed {
+        Ok(cctray_projects) => {
+            data.last_good_feeds.insert(key, cctray_projects.clone());
+            Ok(feed_response(req, query, cctray_projects))
+        }
+        Err(FeedError::Upstream(e)) if e.is_unavailable() => match data.last_good_feeds.get(&key) {
+            Some((cctray_projects, age)) => {
+                warn!("Serving the last good feed for {}: {}", req.path(), e);
+                let mut response = feed_response(req, query, cctray_projects);
+                let headers = response.headers_mut();
+                headers.insert(WARNING, HeaderValue::from_static("110 - \"Response is Stale\""));
+                headers.insert(AGE, HeaderValue::from(age.as_secs()));
+                Ok(response)
+            }
+            None => Err(FeedError::Upstream(e).into()),
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+/*
+ * Polled projects are served from memory, whatever the token of the request, as polled orgs
+ * always have a configured token, which requests without one are served with anyway. A feed
+ * which hasn't been refreshed for two intervals is marked as stale.
+ */
+fn serve_polled_feed(
+    req: &HttpRequest,
+    info: &ProjectInfo,
+    query: &FeedQuery,
+    data: &AppState,
+) -> Option<HttpResponse> {
+    let (cctray_projects, age) = get_polled_feed(data, info, query)?;
+    let mut response = feed_response(req, query, cctray_projects);
+
+    if age > data.poller.interval * 2 {
+        let headers = response.headers_mut();
+        headers.insert(WARNING, HeaderValue::from_static("110 - \"Response is Stale\""));
+        headers.insert(AGE, HeaderValue::from(age.as_secs()));
+    }
+
+    Some(response)
+}
+
+/// The feed of a polled project, and the time since it was polled, when the query allows it.
+fn get_polled_feed(
+    data: &AppState,
+    info: &ProjectInfo,
+    query: &FeedQuery,
+) -> Option<(Vec<CCTrayProjectInfo>, Duration)> {
+    if !query.is_default_feed() {
+        return None;
+    }
+
+    let polled = data.polled_feeds.get(&info.org, &info.project)?;
+    let pipelines = data.webhook_pipelines.merge_pipelines(&polled.project_id, polled.pipelines);
+    let config = get_project_config(data, &info.org, &polled.project_name, &polled.project_id);
+    let options = get_feed_options(data, config, &polled.project_name, query);
+    let cctray_projects = cctray::to_cctray_project_info(pipelines, &info.org, &options);
+    let mut cctray_projects = filter_by_name(config, query, cctray_projects);
+
+    if let Some(auth_token) = data.auth.tokens.get(&info.org) {
+        let base_url = get_base_url(data, &info.org);
+        details::add_known_messages(data, &base_url, auth_token, &mut cctray_projects);
+    }
+
+    Some((cctray_projects, polled.polled_at.elapsed()))
 }
 
-fn to_actix_error(e: reqwest::Error) -> actix_web::Error {
+fn feed_response(
+    req: &HttpRequest,
+    query: &FeedQuery,
+    cctray_projects: Vec<CCTrayProjectInfo>,
+) -> HttpResponse {
+    let format = query.format.unwrap_or_else(|| {
+        match Accept::parse(req).map(|accept| accept.preference()) {
+            Ok(mime) if mime == mime::APPLICATION_JSON => Format::Json,
+            _ => Format::Xml,
+        }
+    });
+
+    /* The format depends on the `Accept` header, so caches must not serve one for the other */
+    match format {
+        Format::Xml => HttpResponse::Ok()
+            .content_type(ContentType::xml())
+            .insert_header((VARY, "Accept"))
+            .body(cctray::serialize(cctray_projects)),
+        Format::Json => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .insert_header((VARY, "Accept"))
+            .body(cctray::serialize_json(cctray_projects)),
+    }
+}
+
+async fn get_cctray_projects(
+    data: &AppState,
+    base_url: &str,
+    project: &Project,
+    auth_token: &str,
+    org: &str,
+    query: &FeedQuery,
+) -> Result<Vec<CCTrayProjectInfo>, semaphoreci::Error> {
+    let project_id = &project.metadata.id;
+    let config = get_project_config(data, org, &project.metadata.name, project_id);
+    let options = &get_feed_options(data, config, &project.metadata.name, query);
+
+    let cctray_projects = match query.group_by {
+        GroupBy::Pipeline => {
+            let pipelines = get_pipelines(data, base_url, project_id, auth_token).await?;
+
+            cctray::to_cctray_project_info(filter_by_branch(pipelines, query), org, options)
+        }
+        GroupBy::PipelineAndBranch => {
+            let pipelines = get_pipelines(data, base_url, project_id, auth_token).await?;
+
+            cctray::to_cctray_project_info_by_pipeline_and_branch(
+                filter_by_branch(pipelines, query),
+                org,
+                options,
+            )
+        }
+        GroupBy::Workflow => {
+            let (pipelines, mut workflows) = try_join(
+                get_pipelines(data, base_url, project_id, auth_token),
+                get_workflows(data, base_url, project_id, auth_token),
+            )
+            .await?;
+
+            if let Some(branch) = &query.branch {
+                workflows.retain(|w| &w.branch_name == branch);
+            }
+
+            cctray::to_cctray_project_info_by_workflow(pipelines, workflows, org, options)
+        }
+    };
+
+    /* Metrics are recorded per pipeline, before the projects are split into blocks or jobs */
+    record_build_statuses(&data.metrics, org, &project.metadata.name, &cctray_projects);
+
+    let cctray_projects = match query.granularity {
+        Granularity::Pipeline => cctray_projects,
+        Granularity::Block | Granularity::Job => {
+            let jobs = query.granularity == Granularity::Job;
+            details::split_by_task(data, base_url, auth_token, cctray_projects, jobs, options).await
+        }
+    };
+
+    let mut cctray_projects = filter_by_name(config, query, cctray_projects);
+    details::add_messages(data, base_url, auth_token, &mut cctray_projects).await;
+
+    Ok(cctray_projects)
+}
+
+fn record_build_statuses(metrics: &Metrics, org: &str, project: &str, cctray_projects: &[CCTrayProjectInfo]) {
+    let counts = cctray_projects.iter().counts_by(|p| p.last_build_status);
+
+    for status in BuildStatus::ALL {
+        let count = counts.get(&status).copied().unwrap_or(0);
+        metrics
+            .projects
+            .with_label_values(&[org, project, status.as_str()])
+            .set(count as i64);
+    }
+}
+
+/// The settings of a SemaphoreCI project, configured by name or by id.
+fn get_project_config<'a>(
+    data: &'a AppState,
+    org: &str,
+    project_name: &str,
+    project_id: &str,
+) -> Option<&'a ProjectConfig> {
+    data.projects
+        .get(&format!("{}/{}", org, project_name))
+        .or_else(|| data.projects.get(&format!("{}/{}", org, project_id)))
+}
+
+/// The name template of the query, otherwise the one configured for the project or all feeds.
+fn get_name_template<'a>(
+    data: &'a AppState,
+    config: Option<&'a ProjectConfig>,
+    query: &'a FeedQuery,
+) -> Option<&'a NameTemplate> {
+    query
+        .name
+        .as_ref()
+        .or(config.and_then(|c| c.name_template.as_ref()))
+        .or(data.feed_options.name_template.as_ref())
+}
+
+/// The options of the feed of a project, with the templates of the query, the project or all feeds.
+fn get_feed_options(
+    data: &AppState,
+    config: Option<&ProjectConfig>,
+    project_name: &str,
+    query: &FeedQuery,
+) -> FeedOptions {
+    FeedOptions {
+        name_template: get_name_template(data, config, query).cloned(),
+        label_template: query
+            .label
+            .as_ref()
+            .or(config.and_then(|c| c.label.as_ref()))
+            .or(data.feed_options.label_template.as_ref())
+            .cloned(),
+        project: project_name.to_string(),
+        hide_stale_promotions: query
+            .promotions
+            .map_or(data.feed_options.hide_stale_promotions, |p| p == Promotions::Current),
+        ..data.feed_options.clone()
+    }
+}
+
+/*
+ * Filters the cctray projects of a SemaphoreCI project by name. The patterns of the query take
+ * precedence over the ones configured for the project.
+ */
+fn filter_by_name(
+    config: Option<&ProjectConfig>,
+    query: &FeedQuery,
+    cctray_projects: Vec<CCTrayProjectInfo>,
+) -> Vec<CCTrayProjectInfo> {
+    let include = query
+        .include
+        .as_deref()
+        .or(config.map(|c| c.include.as_slice()))
+        .unwrap_or_default();
+    let exclude = query
+        .exclude
+        .as_deref()
+        .or(config.map(|c| c.exclude.as_slice()))
+        .unwrap_or_default();
+
+    cctray::filter_by_name(cctray_projects, include, exclude)
+}
+
+fn filter_by_branch(mut pipelines: Vec<Pipeline>, query: &FeedQuery) -> Vec<Pipeline> {
+    if let Some(branch) = &query.branch {
+        pipelines.retain(|p| p.branch_name.as_ref() == Some(branch));
+    }
+    pipelines
+}
+
+async fn get_projects(
+    data: &AppState,
+    base_url: &str,
+    auth_token: &str,
+) -> Result<Vec<Project>, semaphoreci::Error> {
+    let key = CacheKey::new(base_url, auth_token, None);
+    let client = data.client.clone();
+    let base_url = base_url.to_string();
+    let auth_token = auth_token.to_string();
+
+    data.projects_cache
+        .get_or_fetch(key, move || {
+            let (client, base_url, auth_token) = (client.clone(), base_url.clone(), auth_token.clone());
+            async move { semaphoreci::get_projects(&base_url, &auth_token, &client).await }
+        })
+        .await
+}
+
+/// The pipelines of a project, including the ones received through webhooks.
+async fn get_pipelines(
+    data: &AppState,
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
+) -> Result<Vec<Pipeline>, semaphoreci::Error> {
+    let pipelines = get_cached_pipelines(data, base_url, project_id, auth_token).await?;
+
+    Ok(data.webhook_pipelines.merge_pipelines(project_id, pipelines))
+}
+
+async fn get_cached_pipelines(
+    data: &AppState,
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
+) -> Result<Vec<Pipeline>, semaphoreci::Error> {
+    let key = CacheKey::new(base_url, auth_token, Some(project_id));
+    let client = data.client.clone();
+    let base_url = base_url.to_string();
+    let project_id = project_id.to_string();
+    let auth_token = auth_token.to_string();
+
+    data.pipelines_cache
+        .get_or_fetch(key, move || {
+            let (client, base_url, project_id, auth_token) =
+                (client.clone(), base_url.clone(), project_id.clone(), auth_token.clone());
+            async move { semaphoreci::get_pipelines(&base_url, &project_id, &auth_token, &client).await }
+        })
+        .await
+}
+
+/// The workflows of a project, including the ones of pipelines received through webhooks.
+async fn get_workflows(
+    data: &AppState,
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
+) -> Result<Vec<Workflow>, semaphoreci::Error> {
+    let workflows = get_cached_workflows(data, base_url, project_id, auth_token).await?;
+
+    Ok(data.webhook_pipelines.merge_workflows(project_id, workflows))
+}
+
+async fn get_cached_workflows(
+    data: &AppState,
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
+) -> Result<Vec<Workflow>, semaphoreci::Error> {
+    let key = CacheKey::new(base_url, auth_token, Some(project_id));
+    let client = data.client.clone();
+    let base_url = base_url.to_string();
+    let project_id = project_id.to_string();
+    let auth_token = auth_token.to_string();
+
+    data.workflows_cache
+        .get_or_fetch(key, move || {
+            let (client, base_url, project_id, auth_token) =
+                (client.clone(), base_url.clone(), project_id.clone(), auth_token.clone());
+            async move { semaphoreci::get_workflows(&base_url, &project_id, &auth_token, &client).await }
+        })
+        .await
+}
+
+fn get_base_url(data: &AppState, org: &str) -> String {
+    data.base_url
+        .clone()
+        .unwrap_or(format!("https://{}.semaphoreci.com", org))
+}
+
+fn to_actix_error(e: semaphoreci::Error) -> actix_web::Error {
     match e.status() {
         Some(reqwest::StatusCode::UNAUTHORIZED) => error::ErrorUnauthorized(e),
         Some(reqwest::StatusCode::NOT_FOUND) => error::ErrorNotFound(e),
+        _ if matches!(e, semaphoreci::Error::CircuitOpen(_)) => error::ErrorServiceUnavailable(e),
         _ => error::ErrorBadGateway(e),
     }
 }
 
-fn get_token(headers: &HeaderMap) -> Result<String, &'static str> {
+/*
+ * Requests are authenticated with the token in their `Authorization` header, falling back to the
+ * token configured for the organisation. Clients which can't set headers can then use the feeds,
+ * as long as a token is configured for their organisation.
+ */
+fn get_token(headers: &HeaderMap, org: &str, auth: &AuthConfig) -> Result<String, &'static str> {
+    let header_token = if auth.header_passthrough {
+        get_header_token(headers).transpose()
+    } else {
+        None
+    };
+
+    header_token.unwrap_or_else(|| {
+        auth.tokens.get(org).cloned().ok_or(if auth.header_passthrough {
+            "Authorization header missing"
+        } else {
+            "No token configured for the organisation"
+        })
+    })
+}
+
+fn get_header_token(headers: &HeaderMap) -> Result<Option<String>, &'static str> {
     headers
         .get("authorization")
-        .ok_or("Authorization header missing")
-        .and_then(|auth_header| {
+        .map(|auth_header| {
             auth_header
                 .to_str()
                 .map_err(|_| "Authorization header is invalid")
+                .map(|auth_token| auth_token.replace("Bearer", "").trim().into())
         })
-        .map(|auth_token| auth_token.replace("Bearer", "").trim().into())
+        .transpose()
 }
 
-pub fn configure_app(cfg: &mut web::ServiceConfig, base_url: &Option<String>) {
-    let client = reqwest::Client::new();
+/*
+ * Counts and times the requests served, by route pattern rather than path, so that the number of
+ * series doesn't grow with the number of organisations and projects.
+ */
+async fn track_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> actix_web::Result<ServiceResponse<impl MessageBody>> {
+    let started_at = Instant::now();
+    let metrics = req.app_data::<web::Data<AppState>>().map(|data| data.metrics.clone());
+    let method = req.method().to_string();
 
-    cfg.app_data(web::Data::new(AppState {
-        client,
-        base_url: base_url.clone(),
-    }))
-    .service(hello)
-    .service(cctray_project);
+    let res = next.call(req).await?;
+
+    if let Some(metrics) = metrics {
+        let route = res.request().match_pattern().unwrap_or_else(|| String::from("unmatched"));
+        let status = res.status().as_u16().to_string();
+
+        metrics
+            .http_requests
+            .with_label_values(&[&route, &method, &status])
+            .inc();
+        metrics
+            .http_request_duration
+            .with_label_values(&[&route, &method])
+            .observe(started_at.elapsed().as_secs_f64());
+    }
+
+    Ok(res)
+}
+
+/// Starts the tasks running alongside the server, ie. the poller when projects are configured.
+pub fn spawn_background_tasks(state: &web::Data<AppState>) {
+    if !state.poller.targets.is_empty() {
+        actix_web::rt::spawn(poller::run(
+            state.clone(),
+            state.poller.targets.clone(),
+            state.poller.interval,
+        ));
+    }
+}
+
+pub fn configure_app(cfg: &mut web::ServiceConfig, state: &web::Data<AppState>) {
+    cfg.app_data(state.clone()).service(
+        web::scope("")
+            .wrap(from_fn(track_requests))
+            .service(hello)
+            .service(healthz)
+            .service(ready)
+            .service(prometheus_metrics)
+            .service(semaphoreci_webhook)
+            .service(cctray_org)
+            .service(cctray_project)
+            .service(project_events),
+    );
 }
 
@@ -1,24 +1,108 @@
-use actix_web::{App, HttpServer};
-use semaphoreci_cctray::configure_app;
+use actix_web::{web, App, HttpServer};
+use semaphoreci_cctray::config::{Config, CONFIG_PATH_ENV_VAR};
+use semaphoreci_cctray::{configure_app, spawn_background_tasks, AppState};
 use std::env;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::exit;
 use actix_web::middleware::Logger;
 
+const USAGE: &str = "Usage: semaphoreci-cctray [--config <path>] [--print-config] [--healthcheck]
+
+Options:
+  --config <path>  TOML configuration file, defaults to the SEMAPHORECI_CCTRAY_CONFIG env var
+  --print-config   prints the effective configuration, with secrets redacted, and exits
+  --healthcheck    checks that the server of this configuration is up, exiting with 1 when it isn't
+  --help           prints this message and exits";
+
+#[derive(Default)]
+struct Args {
+    config_path: Option<PathBuf>,
+    print_config: bool,
+    healthcheck: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => {
+                let path = args.next().ok_or("Missing path after --config")?;
+                parsed.config_path = Some(PathBuf::from(path));
+            }
+            "--print-config" => parsed.print_config = true,
+            "--healthcheck" => parsed.healthcheck = true,
+            "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ => match arg.strip_prefix("--config=") {
+                Some(path) => parsed.config_path = Some(PathBuf::from(path)),
+                None => return Err(format!("Unknown argument {}", arg)),
+            },
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// The liveness check of the server listening on `server.bind_ip` and `server.port`, reached on
+/// the loopback address when listening on all interfaces.
+async fn healthcheck(config: &Config) -> Result<(), String> {
+    let host = match config.server.bind_ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) if ip.is_unspecified() => String::from("127.0.0.1"),
+        Ok(IpAddr::V6(ip)) if ip.is_unspecified() => String::from("[::1]"),
+        Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+        _ => config.server.bind_ip.clone(),
+    };
+    let url = format!("http://{}:{}/healthz", host, config.server.port);
+
+    let response = reqwest::get(&url).await.map_err(|e| format!("{} is unreachable: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} responded with {}", url, response.status()));
+    }
+    Ok(())
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let port = env::var("PORT")
-        .ok()
-        .and_then(|port| port.parse::<u16>().ok())
-        .unwrap_or(8080);
-    let bind_ip = env::var("BIND_IP")
-        .ok()
-        .unwrap_or(String::from("127.0.0.1"));
-    let ci_base_url = env::var("CI_BASE_URL").ok();
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        exit(2);
+    });
+    let config_path = args
+        .config_path
+        .or_else(|| env::var_os(CONFIG_PATH_ENV_VAR).map(PathBuf::from));
+
+    let config = Config::load(config_path.as_deref(), |name| env::var(name).ok())
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        });
+
+    if args.print_config {
+        print!("{}", config.to_redacted_toml());
+        return Ok(());
+    }
+
+    if args.healthcheck {
+        if let Err(e) = healthcheck(&config).await {
+            eprintln!("{}", e);
+            exit(1);
+        }
+        return Ok(());
+    }
+
+    let bind_address = (config.server.bind_ip.clone(), config.server.port);
+    let state = web::Data::new(AppState::new(&config));
+    spawn_background_tasks(&state);
 
     HttpServer::new(move || App::new().wrap(Logger::default())
-        .configure(|cfg| configure_app(cfg, &ci_base_url)))
-        .bind((bind_ip, port))?
+        .configure(|cfg| configure_app(cfg, &state)))
+        .bind(bind_address)?
         .run()
         .await
 }
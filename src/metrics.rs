@@ -0,0 +1,147 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+/*
+ * Prometheus metrics of the server. Metrics are registered in their own registry, rather than the
+ * global one, so that several apps can run in the same process (eg. in tests).
+ */
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub upstream_requests: IntCounterVec,
+    pub upstream_request_duration: HistogramVec,
+    cache_requests: IntCounterVec,
+    pub projects: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests served, by route"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to serve HTTP requests, by route",
+            ),
+            &["route", "method"],
+        )
+        .unwrap();
+        let upstream_requests = IntCounterVec::new(
+            Opts::new(
+                "upstream_requests_total",
+                "Requests sent to the SemaphoreCI API, by endpoint and response status",
+            ),
+            &["endpoint", "status"],
+        )
+        .unwrap();
+        let upstream_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_request_duration_seconds",
+                "Time taken by the SemaphoreCI API to respond, by endpoint",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+        let cache_requests = IntCounterVec::new(
+            Opts::new(
+                "cache_requests_total",
+                "Lookups in the cache of SemaphoreCI responses, by result (hit, stale or miss)",
+            ),
+            &["cache", "result"],
+        )
+        .unwrap();
+        let projects = IntGaugeVec::new(
+            Opts::new(
+                "cctray_projects",
+                "cctray projects in the last feed served for a SemaphoreCI project, by build status",
+            ),
+            &["org", "project", "status"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(upstream_requests.clone())).unwrap();
+        registry.register(Box::new(upstream_request_duration.clone())).unwrap();
+        registry.register(Box::new(cache_requests.clone())).unwrap();
+        registry.register(Box::new(projects.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            upstream_requests,
+            upstream_request_duration,
+            cache_requests,
+            projects,
+        }
+    }
+
+    pub fn cache_counters(&self, cache: &str) -> CacheCounters {
+        CacheCounters {
+            hit: self.cache_requests.with_label_values(&[cache, "hit"]),
+            stale: self.cache_requests.with_label_values(&[cache, "stale"]),
+            miss: self.cache_requests.with_label_values(&[cache, "miss"]),
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("Metrics can always be encoded");
+
+        String::from_utf8(buffer).expect("Metrics are encoded as UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+#[derive(Clone)]
+pub struct CacheCounters {
+    pub hit: IntCounter,
+    pub stale: IntCounter,
+    pub miss: IntCounter,
+}
+
+impl Default for CacheCounters {
+    /// Counters which are not registered anywhere.
+    fn default() -> Self {
+        Metrics::new().cache_counters("unregistered")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_metrics_in_text_format() {
+        let metrics = Metrics::new();
+        metrics
+            .http_requests
+            .with_label_values(&["/{org}/{project}/cctray", "GET", "200"])
+            .inc();
+        metrics.cache_counters("pipelines").hit.inc();
+
+        let rendered = metrics.render();
+
+        assert!(rendered.contains(
+            "http_requests_total{method=\"GET\",route=\"/{org}/{project}/cctray\",status=\"200\"} 1"
+        ), "{}", rendered);
+        assert!(rendered.contains("cache_requests_total{cache=\"pipelines\",result=\"hit\"} 1"), "{}", rendered);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::cctray;
+use crate::details;
+use crate::config::PollTarget;
+use crate::semaphoreci::{self, Pipeline};
+use crate::{get_base_url, record_build_statuses, AppState};
+use actix_web::web;
+use itertools::Itertools;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct PolledFeed {
+    pub project_id: String,
+    pub project_name: String,
+    pub pipelines: Vec<Pipeline>,
+    pub polled_at: Instant,
+}
+
+/*
+ * The pipelines of the polled projects, by organisation and project, which their feed is built
+ * from. Each is stored under both the name and the id of its project, as requests can use either.
+ */
+#[derive(Default)]
+pub struct PolledFeeds {
+    feeds: Mutex<HashMap<(String, String), PolledFeed>>,
+}
+
+impl PolledFeeds {
+    pub fn get(&self, org: &str, project: &str) -> Option<PolledFeed> {
+        self.feeds
+            .lock()
+            .unwrap()
+            .get(&(org.to_string(), project.to_string()))
+            .cloned()
+    }
+
+    fn insert(&self, org: &str, project: &semaphoreci::Project, pipelines: Vec<Pipeline>) {
+        let feed = PolledFeed {
+            project_id: project.metadata.id.clone(),
+            project_name: project.metadata.name.clone(),
+            pipelines,
+            polled_at: Instant::now(),
+        };
+        let mut feeds = self.feeds.lock().unwrap();
+
+        feeds.insert((org.to_string(), project.metadata.id.clone()), feed.clone());
+        feeds.insert((org.to_string(), project.metadata.name.clone()), feed);
+    }
+}
+
+/*
+ * Polls the configured projects on an interval, so that their feeds are served from memory. The
+ * load on SemaphoreCI then only depends on the number of projects, rather than on the number of
+ * dashboards. A project whose poll fails keeps its previous feed.
+ */
+pub async fn run(data: web::Data<AppState>, targets: Vec<PollTarget>, interval: Duration) {
+    let mut ticker = actix_web::rt::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        for (org, projects) in targets.iter().into_group_map_by(|target| &target.org) {
+            if let Err(e) = poll_org(&data, org, &projects).await {
+                warn!("Failed to poll the projects of {}: {}", org, e);
+            }
+        }
+    }
+}
+
+async fn poll_org(data: &AppState, org: &str, targets: &[&PollTarget]) -> Result<(), semaphoreci::Error> {
+    let base_url = get_base_url(data, org);
+    let Some(auth_token) = data.auth.tokens.get(org) else {
+        return Ok(());
+    };
+
+    let projects = semaphoreci::get_projects(&base_url, auth_token, &data.client).await?;
+
+    for target in targets {
+        let Some(project) = projects
+            .iter()
+            .find(|p| p.metadata.name == target.project || p.metadata.id == target.project)
+        else {
+            warn!("Polled project {}/{} not found", org, target.project);
+            continue;
+        };
+
+        match semaphoreci::get_pipelines(&base_url, &project.metadata.id, auth_token, &data.client).await {
+            Ok(pipelines) => {
+                let cctray_projects = cctray::to_cctray_project_info(
+                    data.webhook_pipelines.merge_pipelines(&project.metadata.id, pipelines.clone()),
+                    org,
+                    &data.feed_options,
+                );
+                record_build_statuses(&data.metrics, org, &project.metadata.name, &cctray_projects);
+                details::fetch_details(data, &base_url, auth_token, &cctray_projects).await;
+                data.polled_feeds.insert(org, project, pipelines);
+            }
+            Err(e) => warn!("Failed to poll {}/{}: {}", org, target.project, e),
+        }
+    }
+
+    Ok(())
+}
@@ -1,95 +1,509 @@
-use reqwest::header::AUTHORIZATION;
-use reqwest::Client;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::UpstreamConfig;
+use crate::metrics::Metrics;
+use log::{debug, warn};
+use reqwest::header::{HeaderMap, AUTHORIZATION, LINK, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(Default))]
 pub struct Timestamp {
     pub seconds: i64,
 }
 
-#[derive(Deserialize, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(test, derive(Default))]
+#[allow(clippy::upper_case_acronyms)]
 pub enum State {
-    DONE,
+    #[cfg_attr(test, default)]
+    INITIALIZING,
+    PENDING,
+    QUEUING,
     RUNNING,
+    STOPPING,
+    DONE,
     #[serde(untagged)]
     UNKNOWN(String)
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum Result {
     PASSED,
     FAILED,
+    STOPPED,
+    CANCELED,
     #[serde(untagged)]
     UNKNOWN(String)
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+pub enum ResultReason {
+    TEST,
+    MALFORMED,
+    STUCK,
+    DELETED,
+    INTERNAL,
+    USER,
+    STRATEGY,
+    FAST_FAILING,
+    TIMEOUT,
+    #[serde(untagged)]
+    UNKNOWN(String)
+}
+
+impl ResultReason {
+    /// Whether the pipeline didn't run to completion because of an error, rather than a failure
+    /// of the build itself (eg. an invalid yaml file).
+    pub fn is_error(&self) -> bool {
+        matches!(self, ResultReason::MALFORMED | ResultReason::STUCK | ResultReason::INTERNAL)
+    }
+
+    /// Whether the pipeline was stopped by a user or auto-cancelled, usually because a newer
+    /// pipeline superseded it, rather than because something went wrong.
+    pub fn is_superseded(&self) -> bool {
+        matches!(self, ResultReason::USER | ResultReason::STRATEGY)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(Default))]
 pub struct Pipeline {
     pub state: State,
     pub result: Option<Result>,
+    #[serde(default)]
+    pub result_reason: Option<ResultReason>,
     pub name: String,
     pub created_at: Timestamp,
     pub done_at: Timestamp,
     pub ppl_id: String,
     pub wf_id: String,
+    #[serde(default)]
+    pub branch_name: Option<String>,
+    #[serde(default)]
+    pub commit_sha: Option<String>,
+    #[serde(default)]
+    pub workflow_number: Option<u64>,
+    /// The pipeline this one was promoted from, empty unless it was triggered by a promotion.
+    #[serde(default)]
+    pub promotion_of: Option<String>,
+}
+
+impl Pipeline {
+    /// The id of the pipeline this one was promoted from, if any.
+    pub fn promoted_from(&self) -> Option<&str> {
+        self.promotion_of.as_deref().filter(|id| !id.is_empty())
+    }
+
+    /// Whether the pipeline was stopped or cancelled before completing, without an error.
+    pub fn is_superseded(&self) -> bool {
+        matches!(self.result, Some(Result::STOPPED | Result::CANCELED))
+            && self.result_reason.as_ref().is_some_and(ResultReason::is_superseded)
+    }
+
+    /// The git tag the pipeline ran on, which SemaphoreCI reports as a `refs/tags/` branch.
+    pub fn tag_name(&self) -> Option<&str> {
+        self.branch_name.as_deref()?.strip_prefix("refs/tags/")
+    }
+}
+
+/*
+ * A pipeline as described by the `pipelines/{id}?detailed=true` endpoint, with its blocks and
+ * their jobs. Only the fields needed for cctray messages and per block projects are read.
+ */
+#[derive(Deserialize, Debug, Clone)]
+pub struct PipelineDetails {
+    pub pipeline: PipelineDescription,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PipelineDescription {
+    #[serde(default)]
+    pub state: Option<State>,
+    #[serde(default)]
+    pub commit_sha: Option<String>,
+    /// Author of the commit the pipeline ran on. It isn't among the documented fields of the
+    /// pipeline API, so it is read when present, and missing otherwise.
+    #[serde(default)]
+    pub commit_author: Option<String>,
+    #[serde(default)]
+    pub commit_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Block {
+    pub name: String,
+    /// Blocks waiting for the ones they depend on are `WAITING`, read as an unknown state.
+    #[serde(default)]
+    pub state: Option<State>,
+    #[serde(default)]
+    pub result: Option<Result>,
+    #[serde(default)]
+    pub result_reason: Option<ResultReason>,
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+}
+
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum JobStatus {
+    PENDING,
+    QUEUED,
+    RUNNING,
+    FINISHED,
+    #[serde(untagged)]
+    UNKNOWN(String)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    #[serde(default)]
+    pub status: Option<JobStatus>,
+    #[serde(default)]
+    pub result: Option<Result>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Workflow {
+    pub wf_id: String,
+    pub branch_name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ProjectMetadata {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Project {
     pub metadata: ProjectMetadata,
 }
 
+#[derive(Debug)]
+pub enum Error {
+    Http(reqwest::Error),
+    /// Requests to the host are not sent, as it has been failing (see `CircuitBreaker`).
+    CircuitOpen(String),
+}
+
+impl Error {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Http(e) => e.status(),
+            Error::CircuitOpen(_) => None,
+        }
+    }
+
+    /// Whether SemaphoreCI failed to respond properly, rather than rejecting the request.
+    pub fn is_unavailable(&self) -> bool {
+        match self.status() {
+            Some(status) => is_retryable(status),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => e.fmt(f),
+            Error::CircuitOpen(host) => write!(f, "{} is failing, requests are paused", host),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    max_pages: usize,
+    max_pipeline_pages: usize,
+    max_retries: u32,
+    retry_backoff: Duration,
+    max_retry_delay: Duration,
+    circuit_breaker: Arc<CircuitBreaker>,
+    metrics: Arc<Metrics>,
+}
+
+impl Client {
+    pub fn new(config: &UpstreamConfig, metrics: Arc<Metrics>) -> Self {
+        let http = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .build()
+            .expect("The HTTP client can always be built");
+
+        Client {
+            http,
+            max_pages: config.max_pages,
+            max_pipeline_pages: config.max_pipeline_pages,
+            max_retries: config.max_retries,
+            retry_backoff: config.retry_backoff,
+            max_retry_delay: config.max_retry_delay,
+            circuit_breaker: Arc::new(CircuitBreaker::new(
+                config.circuit_breaker_threshold,
+                config.circuit_breaker_cooldown,
+            )),
+            metrics,
+        }
+    }
+}
+
 pub async fn get_projects(
-    base_url: &String,
-    auth_token: &String,
+    base_url: &str,
+    auth_token: &str,
     client: &Client,
-) -> core::result::Result<Vec<Project>, reqwest::Error> {
+) -> core::result::Result<Vec<Project>, Error> {
     let url = format!("{}/api/v1alpha/projects", base_url);
 
-    get(client, url, auth_token).await
+    get_all_pages(client, "projects", url, auth_token, client.max_pages).await
 }
 
 pub async fn get_pipelines(
-    base_url: &String,
-    project_id: &String,
-    auth_token: &String,
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
     client: &Client,
-) -> core::result::Result<Vec<Pipeline>, reqwest::Error> {
+) -> core::result::Result<Vec<Pipeline>, Error> {
     let url = format!(
         "{}/api/v1alpha/pipelines?project_id={}",
         base_url, project_id
     );
 
-    get(client, url, auth_token).await
+    get_all_pages(client, "pipelines", url, auth_token, client.max_pipeline_pages).await
+}
+
+pub async fn get_workflows(
+    base_url: &str,
+    project_id: &str,
+    auth_token: &str,
+    client: &Client,
+) -> core::result::Result<Vec<Workflow>, Error> {
+    let url = format!(
+        "{}/api/v1alpha/plumber-workflows?project_id={}",
+        base_url, project_id
+    );
+
+    get_all_pages(client, "workflows", url, auth_token, client.max_pipeline_pages).await
+}
+
+pub async fn get_pipeline_details(
+    base_url: &str,
+    ppl_id: &str,
+    auth_token: &str,
+    client: &Client,
+) -> core::result::Result<PipelineDetails, Error> {
+    let url = format!("{}/api/v1alpha/pipelines/{}?detailed=true", base_url, ppl_id);
+
+    Ok(get_response(client, "pipeline", url, auth_token).await?.json().await?)
+}
+
+/// Requests the base URL, or the projects when given a token, to check whether SemaphoreCI can be
+/// reached.
+pub async fn get_status(
+    base_url: &str,
+    auth_token: Option<&str>,
+    timeout: Duration,
+    client: &Client,
+) -> core::result::Result<StatusCode, reqwest::Error> {
+    let request = match auth_token {
+        Some(auth_token) => client
+            .http
+            .get(format!("{}/api/v1alpha/projects", base_url))
+            .header(AUTHORIZATION, format!("Token {}", auth_token)),
+        None => client.http.get(base_url),
+    };
+
+    send(client, "status", request.timeout(timeout))
+        .await
+        .map(|response| response.status())
+}
+
+/*
+ * The SemaphoreCI list endpoints are paginated, and advertise the following pages through a
+ * `Link` header. Pages are followed up to `max_pages`, so that a project with a long history
+ * doesn't turn a single feed request into hundreds of upstream calls.
+ */
+async fn get_all_pages<T: DeserializeOwned>(
+    client: &Client,
+    endpoint: &str,
+    url: String,
+    auth_token: &str,
+    max_pages: usize,
+) -> core::result::Result<Vec<T>, Error> {
+    let mut items = Vec::new();
+    let mut next_url = Some(url);
+    let mut pages = 0;
+
+    while let Some(url) = next_url.take() {
+        if pages == max_pages {
+            debug!("Stopped following pagination after {} pages, at {}", pages, url);
+            break;
+        }
+
+        let (page, next_page_url) = get(client, endpoint, url, auth_token).await?;
+        items.extend(page);
+        next_url = next_page_url;
+        pages += 1;
+    }
+
+    Ok(items)
 }
 
 async fn get<T: DeserializeOwned>(
     client: &Client,
+    endpoint: &str,
+    url: String,
+    auth_token: &str,
+) -> core::result::Result<(Vec<T>, Option<String>), Error> {
+    let result = get_response(client, endpoint, url, auth_token).await?;
+
+    let next_page_url = get_next_page_url(result.url(), result.headers());
+
+    Ok((result.json::<Vec<T>>().await?, next_page_url))
+}
+
+/// Sends a request through the circuit breaker of its host, failing on error statuses.
+async fn get_response(
+    client: &Client,
+    endpoint: &str,
     url: String,
-    auth_token: &String,
-) -> core::result::Result<T, reqwest::Error> {
-    let result = client
-        .get(url)
-        .header(AUTHORIZATION, format!("Token {}", auth_token))
-        .send()
-        .await?
-        .error_for_status()?;
+    auth_token: &str,
+) -> core::result::Result<Response, Error> {
+    let host = Url::parse(&url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_default();
+
+    if !client.circuit_breaker.allows(&host) {
+        return Err(Error::CircuitOpen(host));
+    }
+
+    let response = get_with_retries(client, endpoint, &url, auth_token).await;
+
+    match &response {
+        Ok(response) if !is_retryable(response.status()) => client.circuit_breaker.record_success(&host),
+        _ => client.circuit_breaker.record_failure(&host),
+    }
+
+    Ok(response?.error_for_status()?)
+}
+
+/*
+ * Responses with a 5xx or 429 status are retried, after the delay requested with their
+ * `Retry-After` header, or an exponential backoff otherwise. Either is capped to
+ * `max_retry_delay`, so that a feed request doesn't wait on SemaphoreCI for too long.
+ */
+async fn get_with_retries(
+    client: &Client,
+    endpoint: &str,
+    url: &str,
+    auth_token: &str,
+) -> core::result::Result<Response, reqwest::Error> {
+    let mut attempt = 0;
+
+    loop {
+        let request = client
+            .http
+            .get(url)
+            .header(AUTHORIZATION, format!("Token {}", auth_token));
+        let response = send(client, endpoint, request).await?;
+
+        if attempt == client.max_retries || !is_retryable(response.status()) {
+            return Ok(response);
+        }
+
+        let delay = get_retry_after(response.headers())
+            .unwrap_or_else(|| client.retry_backoff.saturating_mul(2u32.saturating_pow(attempt)))
+            .min(client.max_retry_delay);
+        warn!("{} responded with {}, retrying in {:?}", url, response.status(), delay);
+
+        actix_web::rt::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
 
-    result.json::<T>().await
+/// Reads a `Retry-After` header, given either as a number of seconds or as an HTTP date.
+fn get_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => chrono::DateTime::parse_from_rfc2822(value)
+            .ok()
+            .map(|date| (date.to_utc() - chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO)),
+    }
+}
+
+/// Sends a request, recording its status and duration in the upstream metrics.
+async fn send(
+    client: &Client,
+    endpoint: &str,
+    request: RequestBuilder,
+) -> core::result::Result<Response, reqwest::Error> {
+    let started_at = Instant::now();
+    let response = request.send().await;
+
+    let status = response
+        .as_ref()
+        .map_or_else(|_| String::from("error"), |r| r.status().as_u16().to_string());
+    client
+        .metrics
+        .upstream_requests
+        .with_label_values(&[endpoint, &status])
+        .inc();
+    client
+        .metrics
+        .upstream_request_duration
+        .with_label_values(&[endpoint])
+        .observe(started_at.elapsed().as_secs_f64());
+
+    response
+}
+
+fn get_next_page_url(url: &Url, headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let mut parts = link.split(';').map(str::trim);
+            let target = parts.next()?.strip_prefix('<')?.strip_suffix('>')?;
+
+            parts
+                .any(|param| param.replace(' ', "") == "rel=\"next\"")
+                .then(|| url.join(target).ok())
+                .flatten()
+        })
+        .map(String::from)
 }
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
-    use serde_json;
 
     #[test]
     fn test_result_known_value_deserialised_as_enum() {
@@ -103,4 +517,75 @@ mod tests {
         assert_eq!(result, Result::UNKNOWN("BLAH".to_string()));
     }
 
+    #[test]
+    fn test_result_reason_deserialised_as_enum() {
+        let reason: ResultReason = serde_json::from_str("\"MALFORMED\"").unwrap();
+        assert_eq!(reason, ResultReason::MALFORMED);
+        assert!(reason.is_error());
+    }
+
+    #[test]
+    fn test_state_known_value_deserialised_as_enum() {
+        let state: State = serde_json::from_str("\"QUEUING\"").unwrap();
+        assert_eq!(state, State::QUEUING);
+    }
+
+    #[test]
+    fn test_next_page_url_read_from_link_header() {
+        let url = Url::parse("https://org.semaphoreci.com/api/v1alpha/projects").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            "<https://org.semaphoreci.com/api/v1alpha/projects?page=1>; rel=\"first\", <https://org.semaphoreci.com/api/v1alpha/projects?page=2>; rel=\"next\""
+                .parse()
+                .unwrap(),
+        );
+
+        assert_eq!(
+            get_next_page_url(&url, &headers),
+            Some("https://org.semaphoreci.com/api/v1alpha/projects?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_relative_next_page_url_resolved_against_request_url() {
+        let url = Url::parse("https://org.semaphoreci.com/api/v1alpha/projects").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, "</api/v1alpha/projects?page=2>; rel=\"next\"".parse().unwrap());
+
+        assert_eq!(
+            get_next_page_url(&url, &headers),
+            Some("https://org.semaphoreci.com/api/v1alpha/projects?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_retry_after_read_as_seconds() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+
+        assert_eq!(get_retry_after(&headers), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_retry_after_read_as_http_date() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+
+        assert_eq!(get_retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_no_next_page_url_on_last_page() {
+        let url = Url::parse("https://org.semaphoreci.com/api/v1alpha/projects").unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            "<https://org.semaphoreci.com/api/v1alpha/projects?page=1>; rel=\"first\""
+                .parse()
+                .unwrap(),
+        );
+
+        assert_eq!(get_next_page_url(&url, &headers), None);
+    }
 }
@@ -0,0 +1,294 @@
+use crate::semaphoreci::{Pipeline, Timestamp, Workflow};
+use chrono::DateTime;
+use hmac::{Hmac, Mac};
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Header holding the HMAC-SHA256 of the request body, signed with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "X-Semaphore-Signature-256";
+
+/// Webhook pipelines only need to cover those finished since the API was last fetched, so a
+/// project keeps its most recently created ones, plenty for a busy project between two fetches.
+const MAX_PIPELINES_PER_PROJECT: usize = 100;
+
+/// Checks the hex encoded signature of a body, optionally prefixed with `sha256=`.
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(body);
+
+    mac.verify_slice(&signature).is_ok()
+}
+
+/*
+ * The notification SemaphoreCI sends when a pipeline is done. Only the fields needed to build a
+ * `semaphoreci::Pipeline` are read. Unlike the API, states and results are lowercase, and times
+ * are RFC 3339 strings.
+ */
+#[derive(Deserialize, Debug)]
+pub struct Payload {
+    pub project: PayloadProject,
+    pub revision: PayloadRevision,
+    pub workflow: PayloadWorkflow,
+    pub pipeline: PayloadPipeline,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PayloadProject {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PayloadRevision {
+    pub branch: Option<PayloadBranch>,
+    #[serde(default)]
+    pub commit_sha: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PayloadBranch {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PayloadWorkflow {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PayloadPipeline {
+    pub id: String,
+    pub name: String,
+    pub state: String,
+    #[serde(default)]
+    pub result: Option<String>,
+    #[serde(default)]
+    pub result_reason: Option<String>,
+    pub created_at: String,
+    #[serde(default)]
+    pub done_at: Option<String>,
+}
+
+impl Payload {
+    pub fn branch_name(&self) -> Option<String> {
+        self.revision.branch.as_ref().map(|branch| branch.name.clone())
+    }
+
+    pub fn to_pipeline(&self) -> Pipeline {
+        let pipeline = &self.pipeline;
+
+        Pipeline {
+            state: parse_uppercase(&pipeline.state),
+            result: pipeline.result.as_deref().filter(|r| !r.is_empty()).map(parse_uppercase),
+            result_reason: pipeline
+                .result_reason
+                .as_deref()
+                .filter(|r| !r.is_empty())
+                .map(parse_uppercase),
+            name: pipeline.name.clone(),
+            created_at: parse_timestamp(Some(&pipeline.created_at)),
+            done_at: parse_timestamp(pipeline.done_at.as_deref()),
+            ppl_id: pipeline.id.clone(),
+            wf_id: self.workflow.id.clone(),
+            branch_name: self.branch_name(),
+            commit_sha: self.revision.commit_sha.clone(),
+            workflow_number: None,
+            promotion_of: None,
+        }
+    }
+}
+
+/// Reads a lowercase value into one of the API enums, which fall back to an unknown variant.
+fn parse_uppercase<T: DeserializeOwned>(value: &str) -> T {
+    T::deserialize(value.to_uppercase().into_deserializer())
+        .unwrap_or_else(|_: serde::de::value::Error| unreachable!("API enums accept any string"))
+}
+
+fn parse_timestamp(value: Option<&str>) -> Timestamp {
+    Timestamp {
+        seconds: value
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .map_or(0, |dt| dt.timestamp()),
+    }
+}
+
+#[derive(Default)]
+struct ProjectPipelines {
+    pipelines: Vec<Pipeline>,
+    branches_by_workflow: HashMap<String, String>,
+}
+
+/*
+ * Pipelines received through webhooks, by SemaphoreCI project id. They are merged into the
+ * pipelines fetched from the API, which may be cached or polled, so that feeds reflect finished
+ * pipelines as soon as SemaphoreCI notifies about them.
+ */
+#[derive(Default)]
+pub struct WebhookPipelines {
+    projects: Mutex<HashMap<String, ProjectPipelines>>,
+}
+
+impl WebhookPipelines {
+    pub fn insert(&self, payload: &Payload) {
+        let pipeline = payload.to_pipeline();
+        let mut projects = self.projects.lock().unwrap();
+        let project = projects.entry(payload.project.id.clone()).or_default();
+
+        if let Some(branch) = payload.branch_name() {
+            project.branches_by_workflow.insert(pipeline.wf_id.clone(), branch);
+        }
+        project.pipelines.retain(|p| p.ppl_id != pipeline.ppl_id);
+        project.pipelines.push(pipeline);
+
+        if project.pipelines.len() > MAX_PIPELINES_PER_PROJECT {
+            project.pipelines.sort_by_key(|p| std::cmp::Reverse(p.created_at.seconds));
+            project.pipelines.truncate(MAX_PIPELINES_PER_PROJECT);
+
+            let workflow_ids: Vec<&String> = project.pipelines.iter().map(|p| &p.wf_id).collect();
+            project
+                .branches_by_workflow
+                .retain(|wf_id, _| workflow_ids.contains(&wf_id));
+        }
+    }
+
+    /*
+     * Replaces the pipelines also received through webhooks, and adds the ones missing. Webhooks
+     * don't tell which pipeline a promotion was triggered from, so that is kept from the fetched
+     * pipeline.
+     */
+    pub fn merge_pipelines(&self, project_id: &str, mut pipelines: Vec<Pipeline>) -> Vec<Pipeline> {
+        if let Some(project) = self.projects.lock().unwrap().get(project_id) {
+            for received in &project.pipelines {
+                let fetched = pipelines
+                    .iter()
+                    .position(|p| p.ppl_id == received.ppl_id)
+                    .map(|i| pipelines.remove(i));
+
+                pipelines.push(Pipeline {
+                    promotion_of: fetched.and_then(|p| p.promotion_of).or_else(|| received.promotion_of.clone()),
+                    ..received.clone()
+                });
+            }
+        }
+        pipelines
+    }
+
+    /// Adds the workflows of pipelines received through webhooks, when missing.
+    pub fn merge_workflows(&self, project_id: &str, mut workflows: Vec<Workflow>) -> Vec<Workflow> {
+        if let Some(project) = self.projects.lock().unwrap().get(project_id) {
+            for (wf_id, branch_name) in &project.branches_by_workflow {
+                if !workflows.iter().any(|w| &w.wf_id == wf_id) {
+                    workflows.push(Workflow {
+                        wf_id: wf_id.clone(),
+                        branch_name: branch_name.clone(),
+                    });
+                }
+            }
+        }
+        workflows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semaphoreci::{Result, State};
+
+    fn payload(ppl_id: &str, result: &str) -> Payload {
+        serde_json::from_value(serde_json::json!({
+            "version": "1.0.0",
+            "organization": {"name": "my-org", "id": "org-id"},
+            "project": {"name": "my-project", "id": "my-project-id"},
+            "revision": {"reference_type": "branch", "branch": {"name": "main"}, "commit_sha": "4f5e6d7c8b9a"},
+            "workflow": {"id": "wf-1", "initial_pipeline_id": ppl_id},
+            "pipeline": {
+                "id": ppl_id,
+                "name": "build",
+                "state": "done",
+                "result": result,
+                "result_reason": "test",
+                "created_at": "2025-03-24T14:30:00Z",
+                "done_at": "2025-03-24T14:35:23Z"
+            }
+        }))
+        .unwrap()
+    }
+
+    fn signature(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn verifies_signature_with_or_without_prefix() {
+        let body = br#"{"pipeline":{}}"#;
+        let signature = signature("secret", body);
+
+        assert!(verify_signature("secret", body, &signature));
+        assert!(verify_signature("secret", body, &format!("sha256={}", signature)));
+        assert!(!verify_signature("other-secret", body, &signature));
+        assert!(!verify_signature("secret", body, "not hex"));
+    }
+
+    #[test]
+    fn converts_payload_to_pipeline() {
+        let pipeline = payload("ppl-1", "failed").to_pipeline();
+
+        assert_eq!(pipeline.state, State::DONE);
+        assert_eq!(pipeline.result, Some(Result::FAILED));
+        assert_eq!(pipeline.done_at.seconds, 1742826923);
+        assert_eq!(pipeline.branch_name, Some(String::from("main")));
+        assert_eq!(pipeline.commit_sha, Some(String::from("4f5e6d7c8b9a")));
+    }
+
+    #[test]
+    fn merges_received_pipelines_by_id() {
+        let webhook_pipelines = WebhookPipelines::default();
+        webhook_pipelines.insert(&payload("ppl-1", "passed"));
+        webhook_pipelines.insert(&payload("ppl-2", "failed"));
+
+        let mut fetched = payload("ppl-1", "").to_pipeline();
+        fetched.state = State::RUNNING;
+
+        let merged = webhook_pipelines.merge_pipelines("my-project-id", vec![fetched]);
+
+        assert_eq!(merged.len(), 2);
+        assert!(merged.iter().all(|p| p.state == State::DONE));
+        assert!(webhook_pipelines.merge_pipelines("other-project-id", vec![]).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_promotions_of_fetched_pipelines() {
+        let webhook_pipelines = WebhookPipelines::default();
+        webhook_pipelines.insert(&payload("ppl-2", "passed"));
+
+        let mut fetched = payload("ppl-2", "").to_pipeline();
+        fetched.promotion_of = Some(String::from("ppl-1"));
+
+        let merged = webhook_pipelines.merge_pipelines("my-project-id", vec![fetched]);
+
+        assert_eq!(merged[0].state, State::DONE);
+        assert_eq!(merged[0].promoted_from(), Some("ppl-1"));
+    }
+
+    #[test]
+    fn merges_workflows_of_received_pipelines() {
+        let webhook_pipelines = WebhookPipelines::default();
+        webhook_pipelines.insert(&payload("ppl-1", "passed"));
+
+        let workflows = webhook_pipelines.merge_workflows("my-project-id", vec![]);
+
+        assert_eq!(workflows[0].wf_id, "wf-1");
+        assert_eq!(workflows[0].branch_name, "main");
+    }
+}
@@ -0,0 +1,91 @@
+mod support;
+
+use reqwest::header::AUTHORIZATION;
+use semaphoreci_cctray::config::{Config, PollTarget};
+use std::time::Duration;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate, Times};
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+fn poller_config(mock_upstream: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config
+        .auth
+        .tokens
+        .insert(String::from("any-org"), String::from("server-token"));
+    config.poller.targets = vec![PollTarget {
+        org: String::from("any-org"),
+        project: String::from("my-project"),
+    }];
+    config
+}
+
+async fn get_cctray(addr: &std::net::SocketAddr, path_and_query: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .get(format!("http://{}{}", addr, path_and_query))
+        .send()
+        .await
+        .expect("failed to send request")
+}
+
+async fn mount_upstream(mock_upstream: &MockServer, expected_pipelines_requests: impl Into<Times>) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token server-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(header(AUTHORIZATION, "Token server-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .expect(expected_pipelines_requests)
+        .mount(mock_upstream)
+        .await;
+}
+
+#[actix_web::test]
+async fn serves_polled_projects_from_memory() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream, 1).await;
+
+    let addr = start_app_with_config(poller_config(&mock_upstream)).await;
+
+    let by_name = get_cctray(&addr, "/any-org/my-project/cctray").await;
+    let by_id = get_cctray(&addr, "/any-org/my-project-id/cctray?format=json").await;
+
+    assert_eq!(by_name.status(), 200);
+    assert!(by_name.text().await.unwrap().contains("<Project name=\"build\""));
+    assert_eq!(by_id.status(), 200);
+    assert!(by_id.text().await.unwrap().contains("\"name\":\"build\""));
+}
+
+#[actix_web::test]
+async fn fetches_feeds_with_other_options_on_request() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream, 2).await;
+
+    let addr = start_app_with_config(poller_config(&mock_upstream)).await;
+
+    let res = get_cctray(&addr, "/any-org/my-project/cctray?branch=main").await;
+
+    assert_eq!(res.status(), 200);
+}
+
+#[actix_web::test]
+async fn polls_projects_on_an_interval() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream, 2..).await;
+
+    let mut config = poller_config(&mock_upstream);
+    config.poller.interval = Duration::from_millis(150);
+    let addr = start_app_with_config(config).await;
+
+    actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(get_cctray(&addr, "/any-org/my-project/cctray").await.status(), 200);
+}
@@ -0,0 +1,84 @@
+mod support;
+
+use reqwest::header::AUTHORIZATION;
+use semaphoreci_cctray::config::Config;
+use std::time::Duration;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, get_cctray_with_token};
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+const FEED: &str = "/any-org/my-project/cctray";
+
+#[actix_web::test]
+async fn serves_repeated_requests_from_the_cache() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.cache.ttl = Duration::from_secs(60);
+    let addr = start_app_with_config(config).await;
+
+    let first = get_cctray(addr, FEED).await;
+    let second = get_cctray(addr, FEED).await;
+
+    assert_eq!(first.status(), 200);
+    assert_eq!(second.status(), 200);
+    assert_eq!(first.text().await.unwrap(), second.text().await.unwrap());
+}
+
+#[actix_web::test]
+async fn does_not_serve_cached_responses_to_other_tokens() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token : other-token"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("UNAUTHORIZED"))
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.cache.ttl = Duration::from_secs(60);
+    let addr = start_app_with_config(config).await;
+
+    let first = get_cctray(addr, FEED).await;
+    let second = get_cctray_with_token(addr, FEED, "other-token").await;
+
+    assert_eq!(first.status(), 200);
+    assert_eq!(second.status(), 401);
+}
@@ -0,0 +1,40 @@
+mod support;
+
+use semaphoreci_cctray::config::{Config, ProjectConfig};
+use wiremock::MockServer;
+use support::feed::{get_project_names, start_app_with_pipelines};
+use support::fixtures;
+
+#[actix_web::test]
+async fn get_cctray_with_included_projects() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray?include=dep*").await, ["deploy"]);
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray?include=/^b.*d$/").await, ["build"]);
+}
+
+#[actix_web::test]
+async fn get_cctray_with_excluded_projects() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray?exclude=build,lint").await, ["deploy"]);
+}
+
+#[actix_web::test]
+async fn get_cctray_with_configured_patterns_unless_overridden() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.projects.insert(
+        String::from("any-org/my-project"),
+        ProjectConfig {
+            exclude: vec!["deploy".parse().unwrap()],
+            ..ProjectConfig::default()
+        },
+    );
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), config).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray").await, ["build"]);
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray?exclude=build").await, ["deploy"]);
+}
@@ -0,0 +1,72 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use std::net::SocketAddr;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, get_project_names, start_app_with_pipelines};
+use support::fixtures;
+use support::start_app::start_app;
+
+#[actix_web::test]
+async fn get_cctray_for_all_projects_of_an_organisation() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let res = get_cctray(addr, "/any-org/cctray").await;
+
+    assert_eq!(res.status(), 200);
+    let body = res.text().await.unwrap();
+    let requests = mock_upstream.received_requests().await.unwrap();
+    assert_eq!(requests.iter().filter(|r| r.url.path() == "/api/v1alpha/projects").count(), 1);
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"my-project/deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"my-other-project/build\" activity=\"Sleeping\" lastBuildStatus=\"Success\" lastBuildLabel=\"2f1e2b7c-8f0e-4a57-9d0b-2b1c4f3b6e01\" lastBuildTime=\"2025-03-26T14:41:40+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/5b9b0f4e-4a2c-4d8e-9a51-1f7c3e2d9a10?pipeline_id=2f1e2b7c-8f0e-4a57-9d0b-2b1c4f3b6e01\" category=\"my-other-project\"/><Project name=\"my-project/build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
+}
+
+#[actix_web::test]
+async fn returns_401_for_organisation_feed_when_token_missing() {
+    let mock_upstream = MockServer::start().await;
+
+    let addr = start_app(&mock_upstream.uri()).await;
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/any-org/cctray", addr))
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 401);
+}
+
+async fn start_app_with_failing_projects(mock_upstream: &MockServer, failing_project_ids: &[&str]) -> SocketAddr {
+    let mut config = Config::default();
+    config.upstream.max_retries = 0;
+    let addr = start_app_with_pipelines(mock_upstream, fixtures::pipelines_response_body(), config).await;
+
+    for project_id in failing_project_ids {
+        Mock::given(method("GET"))
+            .and(path("/api/v1alpha/pipelines"))
+            .and(query_param("project_id", *project_id))
+            .respond_with(ResponseTemplate::new(500))
+            .with_priority(1)
+            .mount(mock_upstream)
+            .await;
+    }
+
+    addr
+}
+
+#[actix_web::test]
+async fn leaves_out_projects_whose_pipelines_cannot_be_fetched() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_failing_projects(&mock_upstream, &["my-project-id"]).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/cctray").await, ["my-other-project/build"]);
+}
+
+#[actix_web::test]
+async fn returns_502_for_organisation_feed_when_no_project_can_be_fetched() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_failing_projects(&mock_upstream, &["my-project-id", "my-other-project-id"]).await;
+
+    assert_eq!(get_cctray(addr, "/any-org/cctray").await.status(), 502);
+}
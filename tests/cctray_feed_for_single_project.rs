@@ -1,8 +1,11 @@
 mod support;
 
-use reqwest::header::AUTHORIZATION;
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, VARY};
+use semaphoreci_cctray::config::Config;
+use serde_json::json;
 use wiremock::matchers::{header, method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{cctray_request, get_cctray, start_app_with_pipelines};
 use support::fixtures;
 use support::start_app::start_app;
 
@@ -35,9 +38,9 @@ async fn get_cctray_by_project_name() {
         .expect("failed to send request");
 
     assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()[VARY], "Accept");
     let body = res.text().await.unwrap();
-    assert_eq!(body, "<Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\"/>
-<Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\"/></Projects>");
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
 }
 
 #[actix_web::test]
@@ -70,8 +73,7 @@ async fn get_cctray_by_project_id() {
 
     assert_eq!(res.status(), 200);
     let body = res.text().await.unwrap();
-    assert_eq!(body, "<Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\"/>
-<Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\"/></Projects>");
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
 }
 
 #[actix_web::test]
@@ -104,8 +106,7 @@ async fn get_cctray_with_legacy_path() {
 
     assert_eq!(res.status(), 200);
     let body = res.text().await.unwrap();
-    assert_eq!(body, "<Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\"/>
-<Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\"/></Projects>");
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
 }
 
 #[actix_web::test]
@@ -182,3 +183,105 @@ async fn returns_401_when_upstream_api_returned_401() {
     let body = res.text().await.unwrap();
     assert_eq!(body, "");
 }
+
+#[actix_web::test]
+async fn get_cctray_grouped_by_workflow() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/plumber-workflows"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::workflows_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let res = get_cctray(addr, "/any-org/my-project/cctray?group_by=workflow").await;
+
+    assert_eq!(res.status(), 200);
+    let body = res.text().await.unwrap();
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"main\" activity=\"Building\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
+}
+
+#[actix_web::test]
+async fn returns_400_when_query_parameters_are_invalid() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let queries = [
+        "group_by=colour",
+        "include=/[/",
+        "exclude=/[/",
+        "name={repo}",
+        "label={author}",
+        "granularity=step",
+        "promotions=some",
+    ];
+
+    for query in queries {
+        let res = get_cctray(addr, &format!("/any-org/my-project/cctray?{}", query)).await;
+
+        assert_eq!(res.status(), 400, "{}", query);
+    }
+}
+
+#[actix_web::test]
+async fn get_cctray_as_json_when_requested_with_accept_header() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let res = cctray_request(addr, "/any-org/my-project/cctray")
+        .header(ACCEPT, "application/json")
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(res.headers()[VARY], "Accept");
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body, json!({
+        "projects": [
+            {
+                "name": "deploy",
+                "activity": "Sleeping",
+                "lastBuildStatus": "Failure",
+                "lastBuildLabel": "7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
+                "lastBuildTime": "2025-03-28T16:48:30+00:00",
+                "webUrl": "https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
+                "workflowId": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+                "pipelineId": "7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
+                "category": "my-project"
+            },
+            {
+                "name": "build",
+                "activity": "Building",
+                "lastBuildStatus": "Success",
+                "lastBuildLabel": "87887fa3-ced5-4b9b-aa3c-74e65003e55a",
+                "lastBuildTime": "2025-03-24T14:35:23+00:00",
+                "webUrl": "https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c",
+                "workflowId": "94505eb4-27d2-4d5c-a616-27077ae9ac32",
+                "pipelineId": "0a3e10c1-f046-4959-ae9d-2677a997a72c",
+                "category": "my-project"
+            }
+        ]
+    }));
+}
+
+#[actix_web::test]
+async fn get_cctray_as_json_when_requested_with_format_parameter() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let res = cctray_request(addr, "/any-org/my-project/cctray?format=json")
+        .header(ACCEPT, "application/xml")
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()[CONTENT_TYPE], "application/json");
+    assert_eq!(res.headers()[VARY], "Accept");
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["projects"][0]["name"], "deploy");
+}
@@ -0,0 +1,23 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use wiremock::MockServer;
+use support::feed::{get_project_fields, start_app_with_pipelines};
+use support::fixtures;
+
+#[actix_web::test]
+async fn get_cctray_with_configured_label_unless_overridden() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.feed.label = Some("sha".parse().unwrap());
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), config).await;
+
+    assert_eq!(
+        get_project_fields(addr, "/any-org/my-project/cctray", "lastBuildLabel").await,
+        ["9c1d2e3", "87887fa3-ced5-4b9b-aa3c-74e65003e55a"]
+    );
+    assert_eq!(
+        get_project_fields(addr, "/any-org/my-project/cctray?label={branch}", "lastBuildLabel").await,
+        ["main", "main"]
+    );
+}
@@ -0,0 +1,71 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use serde_json::json;
+use std::net::SocketAddr;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, start_app_with_pipelines};
+use support::fixtures;
+
+async fn start_app_with_failed_pipeline(mock_upstream: &MockServer, details: ResponseTemplate) -> SocketAddr {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines/7ba0d874-33f0-4495-af7c-8cbccb7f56e5"))
+        .and(query_param("detailed", "true"))
+        .respond_with(details)
+        .expect(1)
+        .mount(mock_upstream)
+        .await;
+
+    start_app_with_pipelines(mock_upstream, fixtures::pipelines_response_body(), Config::default()).await
+}
+
+async fn get_body(addr: SocketAddr) -> String {
+    let res = get_cctray(addr, "/any-org/my-project/cctray").await;
+
+    assert_eq!(res.status(), 200);
+    res.text().await.unwrap()
+}
+
+#[actix_web::test]
+async fn get_cctray_with_messages_for_broken_builds() {
+    let mock_upstream = MockServer::start().await;
+    let details = ResponseTemplate::new(200).set_body_json(fixtures::failed_pipeline_details_response_body());
+    let addr = start_app_with_failed_pipeline(&mock_upstream, details).await;
+
+    let body = get_body(addr).await;
+    assert_eq!(body, get_body(addr).await);
+
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"><messages><message kind=\"Breakers\" text=\"alice\"/><message kind=\"FailingTasks\" text=\"Smoke tests\"/></messages></Project><Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=0a3e10c1-f046-4959-ae9d-2677a997a72c\" category=\"my-project\"/></Projects>");
+}
+
+#[actix_web::test]
+async fn get_json_with_the_commit_which_broke_the_build() {
+    let mock_upstream = MockServer::start().await;
+    let details = ResponseTemplate::new(200).set_body_json(fixtures::failed_pipeline_details_response_body());
+    let addr = start_app_with_failed_pipeline(&mock_upstream, details).await;
+
+    let res = get_cctray(addr, "/any-org/my-project/cctray?format=json").await;
+
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(
+        body["projects"][0]["breakingCommit"],
+        json!({
+            "sha": "9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d",
+            "author": "alice",
+            "message": "Deploy to the new cluster"
+        })
+    );
+    assert_eq!(body["projects"][1].get("breakingCommit"), None);
+}
+
+#[actix_web::test]
+async fn get_cctray_without_messages_when_pipeline_details_are_unavailable() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_failed_pipeline(&mock_upstream, ResponseTemplate::new(404)).await;
+
+    let body = get_body(addr).await;
+
+    assert!(!body.contains("<messages>"), "{}", body);
+    assert!(body.contains("<Project name=\"deploy\" activity=\"Sleeping\" lastBuildStatus=\"Failure\""), "{}", body);
+}
@@ -0,0 +1,61 @@
+mod support;
+
+use semaphoreci_cctray::config::{Config, ProjectConfig};
+use wiremock::MockServer;
+use support::feed::{get_project_names, start_app_with_pipelines};
+use support::fixtures;
+
+#[actix_web::test]
+async fn get_cctray_with_name_template() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let names = get_project_names(addr, "/any-org/my-project/cctray?name={project}:{branch}:{pipeline}").await;
+
+    assert_eq!(names, ["my-project:main:deploy", "my-project:main:build"]);
+}
+
+#[actix_web::test]
+async fn get_organisation_cctray_with_name_template() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let names = get_project_names(addr, "/any-org/cctray?name={pipeline} ({project})").await;
+
+    assert_eq!(
+        names,
+        ["deploy (my-project)", "build (my-other-project)", "build (my-project)"]
+    );
+}
+
+#[actix_web::test]
+async fn get_cctray_with_configured_name_templates() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.feed.name_template = Some("{org}/{project}/{pipeline}".parse().unwrap());
+    config.projects.insert(
+        String::from("any-org/my-other-project"),
+        ProjectConfig {
+            name_template: Some("Other {name}".parse().unwrap()),
+            ..ProjectConfig::default()
+        },
+    );
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), config).await;
+
+    let names = get_project_names(addr, "/any-org/cctray").await;
+
+    assert_eq!(
+        names,
+        ["any-org/my-project/deploy", "Other build", "any-org/my-project/build"]
+    );
+}
+
+#[actix_web::test]
+async fn get_cctray_with_a_name_template_without_placeholders() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), Config::default()).await;
+
+    let names = get_project_names(addr, "/any-org/my-project/cctray?name=pipeline").await;
+
+    assert_eq!(names, ["pipeline", "pipeline"]);
+}
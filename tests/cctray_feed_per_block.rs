@@ -0,0 +1,92 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use std::net::SocketAddr;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, get_projects, start_app_with_pipelines};
+use support::fixtures;
+
+/// Starts the app with the details of the pipelines, each of which is expected to be requested once.
+async fn start_app_with_pipeline_details(mock_upstream: &MockServer, config: Config) -> SocketAddr {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines/7ba0d874-33f0-4495-af7c-8cbccb7f56e5"))
+        .and(query_param("detailed", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::failed_pipeline_details_response_body()))
+        .expect(1)
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines/0a3e10c1-f046-4959-ae9d-2677a997a72c"))
+        .and(query_param("detailed", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::running_pipeline_details_response_body()))
+        .expect(1)
+        .mount(mock_upstream)
+        .await;
+
+    start_app_with_pipelines(mock_upstream, fixtures::pipelines_response_body(), config).await
+}
+
+/// The name, activity and last build status of the projects of a feed.
+async fn get_statuses(addr: SocketAddr, path_and_query: &str) -> Vec<[String; 3]> {
+    get_projects(addr, path_and_query)
+        .await
+        .iter()
+        .map(|project| ["name", "activity", "lastBuildStatus"].map(|field| project[field].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[actix_web::test]
+async fn get_cctray_per_block() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipeline_details(&mock_upstream, Config::default()).await;
+
+    assert_eq!(
+        get_statuses(addr, "/any-org/my-project/cctray?granularity=block").await,
+        [
+            ["deploy :: Build image", "Sleeping", "Success"],
+            ["deploy :: Smoke tests", "Sleeping", "Failure"],
+            ["build :: Compile", "Building", "Unknown"],
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn get_cctray_per_job() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipeline_details(&mock_upstream, Config::default()).await;
+
+    assert_eq!(
+        get_statuses(addr, "/any-org/my-project/cctray?granularity=job&exclude=*api").await,
+        [
+            ["deploy :: Build image :: docker build", "Sleeping", "Success"],
+            ["deploy :: Smoke tests :: web", "Sleeping", "Failure"],
+            ["build :: Compile :: cargo build", "Building", "Unknown"],
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn get_cctray_per_block_with_messages_for_broken_blocks() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipeline_details(&mock_upstream, Config::default()).await;
+
+    let res = get_cctray(addr, "/any-org/my-project/cctray?granularity=block&include=deploy*").await;
+    assert_eq!(res.status(), 200);
+
+    assert_eq!(res.text().await.unwrap(), "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"deploy :: Build image\" activity=\"Sleeping\" lastBuildStatus=\"Success\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"deploy :: Smoke tests\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"><messages><message kind=\"Breakers\" text=\"alice\"/><message kind=\"FailingTasks\" text=\"Smoke tests\"/></messages></Project></Projects>");
+}
+
+#[actix_web::test]
+async fn get_cctray_per_block_with_cached_details_of_running_pipelines() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.cache.ttl = Duration::from_secs(60);
+    let addr = start_app_with_pipeline_details(&mock_upstream, config).await;
+
+    let first = get_statuses(addr, "/any-org/my-project/cctray?granularity=block").await;
+
+    assert_eq!(get_statuses(addr, "/any-org/my-project/cctray?granularity=block").await, first);
+}
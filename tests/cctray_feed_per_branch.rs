@@ -0,0 +1,34 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use std::net::SocketAddr;
+use wiremock::MockServer;
+use support::feed::{get_cctray, start_app_with_pipelines};
+use support::fixtures;
+
+async fn get_body(addr: SocketAddr, query: &str) -> String {
+    let res = get_cctray(addr, &format!("/any-org/my-project/cctray?{}", query)).await;
+
+    assert_eq!(res.status(), 200);
+    res.text().await.unwrap()
+}
+
+#[actix_web::test]
+async fn get_cctray_filtered_by_branch() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::multi_branch_pipelines_response_body(), Config::default()).await;
+
+    let body = get_body(addr, "branch=main").await;
+
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"build\" activity=\"Sleeping\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=87887fa3-ced5-4b9b-aa3c-74e65003e55a\" category=\"my-project\"/></Projects>");
+}
+
+#[actix_web::test]
+async fn get_cctray_with_one_project_per_pipeline_and_branch() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::multi_branch_pipelines_response_body(), Config::default()).await;
+
+    let body = get_body(addr, "group_by=pipeline_and_branch").await;
+
+    assert_eq!(body, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Projects><Project name=\"build [feature/login]\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" lastBuildTime=\"2025-03-28T16:48:30+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/eb86a134-3081-406a-8ca1-d6e376cf9a65?pipeline_id=7ba0d874-33f0-4495-af7c-8cbccb7f56e5\" category=\"my-project\"/><Project name=\"build [main]\" activity=\"Sleeping\" lastBuildStatus=\"Success\" lastBuildLabel=\"87887fa3-ced5-4b9b-aa3c-74e65003e55a\" lastBuildTime=\"2025-03-24T14:35:23+00:00\" webUrl=\"https://any-org.semaphoreci.com/workflows/94505eb4-27d2-4d5c-a616-27077ae9ac32?pipeline_id=87887fa3-ced5-4b9b-aa3c-74e65003e55a\" category=\"my-project\"/></Projects>");
+}
@@ -0,0 +1,39 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use wiremock::MockServer;
+use support::feed::{get_project_names, start_app_with_pipelines};
+use support::fixtures;
+
+#[actix_web::test]
+async fn get_cctray_with_promotions_named_after_their_parent_pipeline() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::promoted_pipelines_response_body(), Config::default()).await;
+
+    assert_eq!(
+        get_project_names(addr, "/any-org/my-project/cctray").await,
+        ["build", "build → deploy-staging"]
+    );
+}
+
+#[actix_web::test]
+async fn get_cctray_without_stale_promotions() {
+    let mock_upstream = MockServer::start().await;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::promoted_pipelines_response_body(), Config::default()).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray?promotions=current").await, ["build"]);
+}
+
+#[actix_web::test]
+async fn get_cctray_with_configured_hidden_promotions_unless_overridden() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.feed.hide_stale_promotions = true;
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::promoted_pipelines_response_body(), config).await;
+
+    assert_eq!(get_project_names(addr, "/any-org/my-project/cctray").await, ["build"]);
+    assert_eq!(
+        get_project_names(addr, "/any-org/my-project/cctray?promotions=all").await,
+        ["build", "build → deploy-staging"]
+    );
+}
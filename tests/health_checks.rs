@@ -0,0 +1,114 @@
+mod support;
+
+use reqwest::header::AUTHORIZATION;
+use semaphoreci_cctray::config::Config;
+use serde_json::{json, Value};
+use std::time::Duration;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::start_app::{start_app, start_app_with_config};
+
+async fn get_json(addr: &std::net::SocketAddr, path: &str) -> (u16, Value) {
+    let response = reqwest::Client::new()
+        .get(format!("http://{}{}", addr, path))
+        .send()
+        .await
+        .expect("failed to send request");
+
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+#[actix_web::test]
+async fn healthz_reports_the_process_alive() {
+    let addr = start_app("http://127.0.0.1:1").await;
+
+    let (status, body) = get_json(&addr, "/healthz").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body, json!({"status": "ok", "checks": [{"name": "process", "status": "ok"}]}));
+}
+
+#[actix_web::test]
+async fn ready_when_upstream_responds() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let addr = start_app(&mock_upstream.uri()).await;
+
+    let (status, body) = get_json(&addr, "/ready").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["checks"][1]["name"], "upstream");
+    assert_eq!(body["checks"][1]["status"], "ok");
+}
+
+#[actix_web::test]
+async fn not_ready_when_upstream_is_unreachable() {
+    let addr = start_app("http://127.0.0.1:1").await;
+
+    let (status, body) = get_json(&addr, "/ready").await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["status"], "error");
+    assert_eq!(body["checks"][1]["status"], "error");
+}
+
+#[actix_web::test]
+async fn not_ready_when_upstream_rejects_the_configured_token() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token health-token"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.health.token = Some(String::from("health-token"));
+    let addr = start_app_with_config(config).await;
+
+    let (status, body) = get_json(&addr, "/ready").await;
+
+    assert_eq!(status, 503);
+    assert_eq!(body["checks"][1]["status"], "error");
+}
+
+#[actix_web::test]
+async fn not_ready_when_upstream_is_too_slow() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.health.timeout = Duration::from_millis(100);
+    let addr = start_app_with_config(config).await;
+
+    let (status, _) = get_json(&addr, "/ready").await;
+
+    assert_eq!(status, 503);
+}
+
+#[actix_web::test]
+async fn ready_check_skipped_without_configured_base_url() {
+    let addr = start_app_with_config(Config::default()).await;
+
+    let (status, body) = get_json(&addr, "/ready").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["checks"][1]["status"], "skipped");
+}
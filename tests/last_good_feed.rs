@@ -0,0 +1,128 @@
+mod support;
+
+use reqwest::header::{AGE, WARNING};
+use semaphoreci_cctray::config::Config;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{cctray_request, get_cctray, get_cctray_with_token};
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+fn config(mock_upstream: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.upstream.max_retries = 0;
+    config
+}
+
+const FEED: &str = "/any-org/my-project/cctray";
+
+/// Answers the first projects request, then fails the following ones with `failure`.
+async fn mount_upstream_failing_after_first_request(mock_upstream: &MockServer, failure: ResponseTemplate) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .up_to_n_times(1)
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(failure)
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(mock_upstream)
+        .await;
+}
+
+#[actix_web::test]
+async fn serves_last_good_feed_when_upstream_fails() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(500)).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    let good = get_cctray(addr, FEED).await;
+    assert_eq!(good.status(), 200);
+    let good_body = good.text().await.unwrap();
+
+    let stale = get_cctray(addr, FEED).await;
+    assert_eq!(stale.status(), 200);
+    assert_eq!(stale.headers().get(WARNING).unwrap(), "110 - \"Response is Stale\"");
+    assert_eq!(stale.headers().get(AGE).unwrap(), "0");
+    assert_eq!(stale.text().await.unwrap(), good_body);
+}
+
+#[actix_web::test]
+async fn does_not_serve_last_good_feed_to_other_tokens() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(500)).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+    assert_eq!(get_cctray_with_token(addr, FEED, "other-token").await.status(), 502);
+}
+
+#[actix_web::test]
+async fn does_not_serve_last_good_feed_when_upstream_rejects_the_token() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(401)).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+    assert_eq!(get_cctray(addr, FEED).await.status(), 401);
+}
+
+#[actix_web::test]
+async fn reports_unknown_status_in_last_good_feed_when_configured() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(500)).await;
+
+    let mut config = config(&mock_upstream);
+    config.fallback.unknown_status = true;
+    let addr = start_app_with_config(config).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+
+    let stale = get_cctray(addr, FEED).await.text().await.unwrap();
+    assert!(stale.contains("lastBuildStatus=\"Unknown\""), "{}", stale);
+    assert!(!stale.contains("lastBuildStatus=\"Success\""), "{}", stale);
+}
+
+#[actix_web::test]
+async fn does_not_serve_feeds_older_than_max_staleness() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(500)).await;
+
+    let mut config = config(&mock_upstream);
+    config.fallback.max_staleness = Duration::from_millis(50);
+    let addr = start_app_with_config(config).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+    actix_web::rt::time::sleep(Duration::from_millis(60)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 502);
+}
+
+#[actix_web::test]
+async fn serves_last_good_feed_whatever_the_order_of_query_parameters() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream_failing_after_first_request(&mock_upstream, ResponseTemplate::new(500)).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+    let get_cctray = |query: &'static str| cctray_request(addr, &format!("/any-org/my-project/cctray?{}", query)).send();
+
+    assert_eq!(get_cctray("branch=main&format=xml&a=1").await.unwrap().status(), 200);
+
+    let stale = get_cctray("a=2&branch=main").await.unwrap();
+    assert_eq!(stale.status(), 200);
+    assert_eq!(stale.headers().get(WARNING).unwrap(), "110 - \"Response is Stale\"");
+}
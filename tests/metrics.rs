@@ -0,0 +1,92 @@
+mod support;
+
+use semaphoreci_cctray::config::Config;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::get_cctray;
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+async fn get_metrics(addr: &std::net::SocketAddr) -> String {
+    let response = reqwest::Client::new()
+        .get(format!("http://{}/metrics", addr))
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/plain; version=0.0.4"
+    );
+    response.text().await.unwrap()
+}
+
+#[actix_web::test]
+async fn exposes_request_upstream_cache_and_project_metrics() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.cache.ttl = Duration::from_secs(60);
+    let addr = start_app_with_config(config).await;
+
+    for _ in 0..2 {
+        let response = get_cctray(addr, "/any-org/my-project/cctray").await;
+        assert_eq!(response.status(), 200);
+    }
+
+    let metrics = get_metrics(&addr).await;
+
+    assert!(metrics.contains(
+        "http_requests_total{method=\"GET\",route=\"/{org}/{project}/cctray\",status=\"200\"} 2"
+    ), "{}", metrics);
+    assert!(metrics.contains("http_request_duration_seconds_count{method=\"GET\",route=\"/{org}/{project}/cctray\"} 2"), "{}", metrics);
+    assert!(metrics.contains("upstream_requests_total{endpoint=\"projects\",status=\"200\"} 1"), "{}", metrics);
+    assert!(metrics.contains("upstream_requests_total{endpoint=\"pipelines\",status=\"200\"} 1"), "{}", metrics);
+    assert!(metrics.contains("upstream_request_duration_seconds_count{endpoint=\"pipelines\"} 1"), "{}", metrics);
+    assert!(metrics.contains("cache_requests_total{cache=\"pipelines\",result=\"miss\"} 1"), "{}", metrics);
+    assert!(metrics.contains("cache_requests_total{cache=\"pipelines\",result=\"hit\"} 1"), "{}", metrics);
+    assert!(metrics.contains("cctray_projects{org=\"any-org\",project=\"my-project\",status=\"Unknown\"} 0"), "{}", metrics);
+}
+
+#[actix_web::test]
+async fn counts_upstream_error_statuses() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.upstream.max_retries = 1;
+    config.upstream.retry_backoff = Duration::from_millis(1);
+    let addr = start_app_with_config(config).await;
+
+    let response = get_cctray(addr, "/any-org/my-project/cctray").await;
+    assert_eq!(response.status(), 502);
+
+    let metrics = get_metrics(&addr).await;
+
+    assert!(metrics.contains("upstream_requests_total{endpoint=\"projects\",status=\"500\"} 2"), "{}", metrics);
+    assert!(metrics.contains(
+        "http_requests_total{method=\"GET\",route=\"/{org}/{project}/cctray\",status=\"502\"} 1"
+    ), "{}", metrics);
+}
@@ -0,0 +1,111 @@
+mod support;
+
+use reqwest::header::CONTENT_TYPE;
+use semaphoreci_cctray::config::Config;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, start_app_with_pipelines};
+use support::fixtures;
+use support::start_app::{start_app, start_app_with_config};
+
+/// Reads the stream until it contains `expected`, failing after a second.
+async fn read_until(response: &mut reqwest::Response, received: &mut String, expected: &str) {
+    actix_web::rt::time::timeout(Duration::from_secs(1), async {
+        while !received.contains(expected) {
+            let chunk = response.chunk().await.unwrap().expect("stream ended");
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("{} not received in {}", expected, received));
+}
+
+#[actix_web::test]
+async fn streams_build_status_changes() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.events.interval = Duration::from_millis(50);
+    let addr = start_app_with_config(config).await;
+
+    let mut response = get_cctray(addr, "/any-org/my-project/events").await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), "text/event-stream");
+
+    let mut received = String::new();
+    read_until(&mut response, &mut received, "event: projects\ndata: {\"projects\":[").await;
+    read_until(&mut response, &mut received, ": no changes\n\n").await;
+
+    let mut finished_pipelines = fixtures::pipelines_response_body();
+    finished_pipelines[0]["state"] = "DONE".into();
+    finished_pipelines[0]["result"] = "FAILED".into();
+    finished_pipelines[0]["done_at"]["seconds"] = 1743180600.into();
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(finished_pipelines))
+        .with_priority(1)
+        .mount(&mock_upstream)
+        .await;
+
+    read_until(&mut response, &mut received, "event: change\n").await;
+
+    assert!(received.contains("\"name\":\"build\",\"activity\":\"Sleeping\",\"lastBuildStatus\":\"Failure\""), "{}", received);
+    assert!(received.contains("\"previousActivity\":\"Building\",\"previousLastBuildStatus\":\"Success\""), "{}", received);
+    assert_eq!(received.matches("event: change").count(), 1, "{}", received);
+}
+
+#[actix_web::test]
+async fn responds_with_an_error_when_project_does_not_exist() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let addr = start_app(&mock_upstream.uri()).await;
+
+    let response = get_cctray(addr, "/any-org/unknown-project/events").await;
+
+    assert_eq!(response.status(), 404);
+}
+
+#[actix_web::test]
+async fn checks_the_feed_once_per_interval_whatever_the_number_of_clients() {
+    let mock_upstream = MockServer::start().await;
+    let mut config = Config::default();
+    config.events.interval = Duration::from_millis(300);
+    let addr = start_app_with_pipelines(&mock_upstream, fixtures::pipelines_response_body(), config).await;
+
+    let mut first = get_cctray(addr, "/any-org/my-project/events").await;
+    let mut second = get_cctray(addr, "/any-org/my-project/events").await;
+
+    let (mut first_received, mut second_received) = (String::new(), String::new());
+    read_until(&mut first, &mut first_received, "event: projects\n").await;
+    read_until(&mut second, &mut second_received, "event: projects\n").await;
+    read_until(&mut first, &mut first_received, ": no changes\n\n").await;
+    read_until(&mut second, &mut second_received, ": no changes\n\n").await;
+
+    /* The first client's initial feed, then a single check for both clients */
+    let requests = mock_upstream.received_requests().await.unwrap();
+    assert_eq!(requests.iter().filter(|r| r.url.path() == "/api/v1alpha/pipelines").count(), 2);
+}
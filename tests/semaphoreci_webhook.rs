@@ -0,0 +1,138 @@
+mod support;
+
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use semaphoreci_cctray::config::Config;
+use serde_json::json;
+use sha2::Sha256;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::get_cctray;
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+const SECRET: &str = "webhook-secret";
+
+fn webhook_body() -> String {
+    json!({
+        "version": "1.0.0",
+        "organization": {"name": "any-org", "id": "any-org-id"},
+        "project": {"name": "my-project", "id": "my-project-id"},
+        "revision": {"reference_type": "branch", "branch": {"name": "main"}},
+        "workflow": {"id": "webhook-workflow-id", "initial_pipeline_id": "webhook-pipeline-id"},
+        "pipeline": {
+            "id": "webhook-pipeline-id",
+            "name": "build",
+            "state": "done",
+            "result": "failed",
+            "result_reason": "test",
+            "created_at": "2030-01-01T10:00:00Z",
+            "done_at": "2030-01-01T10:05:00Z"
+        }
+    })
+    .to_string()
+}
+
+fn sign(body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+async fn post_webhook(addr: &std::net::SocketAddr, body: String, signature: Option<String>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(format!("http://{}/webhooks/semaphoreci", addr))
+        .header(CONTENT_TYPE, "application/json")
+        .body(body);
+    if let Some(signature) = signature {
+        request = request.header("X-Semaphore-Signature-256", signature);
+    }
+
+    request.send().await.expect("failed to send request")
+}
+
+fn webhook_config(base_url: String) -> Config {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(base_url);
+    config.webhook.secret = Some(String::from(SECRET));
+    config
+}
+
+#[actix_web::test]
+async fn updates_cached_feed_with_received_pipelines() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = webhook_config(mock_upstream.uri());
+    config.cache.ttl = Duration::from_secs(60);
+    let addr = start_app_with_config(config).await;
+
+    let get_cctray = || async {
+        get_cctray(addr, "/any-org/my-project/cctray")
+            .await
+            .text()
+            .await
+            .unwrap()
+    };
+
+    let before = get_cctray().await;
+    assert!(before.contains("<Project name=\"build\" activity=\"Building\" lastBuildStatus=\"Success\""), "{}", before);
+
+    let body = webhook_body();
+    let signature = sign(&body);
+    assert_eq!(post_webhook(&addr, body, Some(signature)).await.status(), 204);
+
+    let after = get_cctray().await;
+    assert!(
+        after.contains("<Project name=\"build\" activity=\"Sleeping\" lastBuildStatus=\"Failure\" lastBuildLabel=\"webhook-pipeline-id\""),
+        "{}",
+        after
+    );
+}
+
+#[actix_web::test]
+async fn rejects_notifications_without_valid_signature() {
+    let addr = start_app_with_config(webhook_config(String::from("http://127.0.0.1:1"))).await;
+
+    assert_eq!(post_webhook(&addr, webhook_body(), None).await.status(), 401);
+    assert_eq!(
+        post_webhook(&addr, webhook_body(), Some(sign("another body"))).await.status(),
+        401
+    );
+}
+
+#[actix_web::test]
+async fn rejects_invalid_payloads() {
+    let addr = start_app_with_config(webhook_config(String::from("http://127.0.0.1:1"))).await;
+
+    let body = String::from("{\"pipeline\": {}}");
+    let signature = sign(&body);
+
+    assert_eq!(post_webhook(&addr, body, Some(signature)).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn webhooks_disabled_without_secret() {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(String::from("http://127.0.0.1:1"));
+    let addr = start_app_with_config(config).await;
+
+    let body = webhook_body();
+    let signature = sign(&body);
+
+    assert_eq!(post_webhook(&addr, body, Some(signature)).await.status(), 404);
+}
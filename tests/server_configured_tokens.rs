@@ -0,0 +1,82 @@
+mod support;
+
+use reqwest::header::AUTHORIZATION;
+use semaphoreci_cctray::config::Config;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+async fn mount_upstream(mock_upstream: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token server-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(header(AUTHORIZATION, "Token server-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(mock_upstream)
+        .await;
+}
+
+async fn start_app_with_token(mock_upstream: &MockServer, header_passthrough: bool) -> SocketAddr {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.auth.tokens = HashMap::from([(String::from("any-org"), String::from("server-token"))]);
+    config.auth.header_passthrough = header_passthrough;
+
+    start_app_with_config(config).await
+}
+
+#[actix_web::test]
+async fn uses_configured_token_when_authorization_header_missing() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream).await;
+    let addr = start_app_with_token(&mock_upstream, true).await;
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/any-org/my-project/cctray", addr))
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 200);
+}
+
+#[actix_web::test]
+async fn returns_401_when_no_token_configured_for_organisation() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream).await;
+    let addr = start_app_with_token(&mock_upstream, true).await;
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/other-org/my-project/cctray", addr))
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 401);
+}
+
+#[actix_web::test]
+async fn ignores_authorization_header_when_passthrough_disabled() {
+    let mock_upstream = MockServer::start().await;
+    mount_upstream(&mock_upstream).await;
+    let addr = start_app_with_token(&mock_upstream, false).await;
+
+    let res = reqwest::Client::new()
+        .get(format!("http://{}/any-org/my-project/cctray", addr))
+        .header(AUTHORIZATION, "Bearer: client-token")
+        .send()
+        .await
+        .expect("failed to send request");
+
+    assert_eq!(res.status(), 200);
+}
@@ -0,0 +1,92 @@
+use reqwest::header::AUTHORIZATION;
+use semaphoreci_cctray::config::Config;
+use serde_json::Value;
+use std::net::SocketAddr;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use super::fixtures;
+use super::start_app::start_app_with_config;
+
+/// Starts the app with `pipelines` as the pipelines of `my-project`, and the usual ones for
+/// `my-other-project`.
+#[allow(dead_code)]
+pub async fn start_app_with_pipelines(mock_upstream: &MockServer, pipelines: Value, config: Config) -> SocketAddr {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(pipelines))
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-other-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::other_project_pipelines_response_body()))
+        .mount(mock_upstream)
+        .await;
+
+    let mut config = config;
+    config.upstream.base_url = Some(mock_upstream.uri());
+    start_app_with_config(config).await
+}
+
+/// A feed request with the test token, eg. to add an `Accept` header to.
+#[allow(dead_code)]
+pub fn cctray_request(addr: SocketAddr, path_and_query: &str) -> reqwest::RequestBuilder {
+    cctray_request_with_token(addr, path_and_query, "my-token")
+}
+
+#[allow(dead_code)]
+fn cctray_request_with_token(addr: SocketAddr, path_and_query: &str, token: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new()
+        .get(format!("http://{}{}", addr, path_and_query))
+        .header(AUTHORIZATION, format!("Bearer: {}", token))
+}
+
+#[allow(dead_code)]
+pub async fn get_cctray(addr: SocketAddr, path_and_query: &str) -> reqwest::Response {
+    cctray_request(addr, path_and_query)
+        .send()
+        .await
+        .expect("failed to send request")
+}
+
+#[allow(dead_code)]
+pub async fn get_cctray_with_token(addr: SocketAddr, path_and_query: &str, token: &str) -> reqwest::Response {
+    cctray_request_with_token(addr, path_and_query, token)
+        .send()
+        .await
+        .expect("failed to send request")
+}
+
+/// The projects of a feed, requested in the JSON format.
+#[allow(dead_code)]
+pub async fn get_projects(addr: SocketAddr, path_and_query: &str) -> Vec<Value> {
+    let separator = if path_and_query.contains('?') { '&' } else { '?' };
+    let res = get_cctray(addr, &format!("{}{}format=json", path_and_query, separator)).await;
+    assert_eq!(res.status(), 200);
+
+    let json: Value = res.json().await.unwrap();
+    json["projects"].as_array().unwrap().clone()
+}
+
+/// The values of a field of the projects of a feed, eg. their `name`.
+#[allow(dead_code)]
+pub async fn get_project_fields(addr: SocketAddr, path_and_query: &str, field: &str) -> Vec<String> {
+    get_projects(addr, path_and_query)
+        .await
+        .iter()
+        .map(|project| project[field].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[allow(dead_code)]
+pub async fn get_project_names(addr: SocketAddr, path_and_query: &str) -> Vec<String> {
+    get_project_fields(addr, path_and_query, "name").await
+}
@@ -1,5 +1,6 @@
 use serde_json::{json, Value};
 
+#[allow(dead_code)]
 pub fn projects_response_body() -> Value {
   json!([
     {
@@ -17,6 +18,7 @@ pub fn projects_response_body() -> Value {
   ])
 }
 
+#[allow(dead_code)]
 pub fn pipelines_response_body() -> Value {
     json!([
       {
@@ -31,7 +33,8 @@ pub fn pipelines_response_body() -> Value {
           "nanos": 0
         },
         "ppl_id": "0a3e10c1-f046-4959-ae9d-2677a997a72c",
-        "wf_id": "94505eb4-27d2-4d5c-a616-27077ae9ac32"
+        "wf_id": "94505eb4-27d2-4d5c-a616-27077ae9ac32",
+        "branch_name": "main"
       },
       {
         "name": "deploy",
@@ -46,7 +49,9 @@ pub fn pipelines_response_body() -> Value {
           "nanos": 558706000
         },
         "ppl_id": "7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
-        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65"
+        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+        "branch_name": "main",
+        "commit_sha": "9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d"
       },
       {
         "name": "build",
@@ -61,7 +66,244 @@ pub fn pipelines_response_body() -> Value {
           "nanos": 771318000
         },
         "ppl_id": "87887fa3-ced5-4b9b-aa3c-74e65003e55a",
-        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65"
+        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+        "branch_name": "main"
+      }
+    ])
+}
+
+#[allow(dead_code)]
+pub fn other_project_pipelines_response_body() -> Value {
+    json!([
+      {
+        "name": "build",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1743000000,
+          "nanos": 0
+        },
+        "done_at": {
+          "seconds": 1743000100,
+          "nanos": 0
+        },
+        "ppl_id": "2f1e2b7c-8f0e-4a57-9d0b-2b1c4f3b6e01",
+        "wf_id": "5b9b0f4e-4a2c-4d8e-9a51-1f7c3e2d9a10"
+      }
+    ])
+}
+
+#[allow(dead_code)]
+pub fn older_pipelines_response_body() -> Value {
+    json!([
+      {
+        "name": "lint",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1742800000,
+          "nanos": 0
+        },
+        "done_at": {
+          "seconds": 1742800100,
+          "nanos": 0
+        },
+        "ppl_id": "c6a4f6d2-3f52-4a8e-8d3e-0f3b1e9d7a21",
+        "wf_id": "d0c1e2f3-a4b5-4c6d-8e7f-9a0b1c2d3e4f"
+      }
+    ])
+}
+
+#[allow(dead_code)]
+pub fn workflows_response_body() -> Value {
+    json!([
+      {
+        "wf_id": "94505eb4-27d2-4d5c-a616-27077ae9ac32",
+        "initial_ppl_id": "0a3e10c1-f046-4959-ae9d-2677a997a72c",
+        "branch_name": "main",
+        "created_at": {
+          "seconds": 1743180511,
+          "nanos": 682810000
+        }
+      },
+      {
+        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+        "initial_ppl_id": "87887fa3-ced5-4b9b-aa3c-74e65003e55a",
+        "branch_name": "main",
+        "created_at": {
+          "seconds": 1742826854,
+          "nanos": 852498000
+        }
+      }
+    ])
+}
+
+#[allow(dead_code)]
+pub fn multi_branch_pipelines_response_body() -> Value {
+    json!([
+      {
+        "name": "build",
+        "state": "DONE",
+        "result": "FAILED",
+        "created_at": {
+          "seconds": 1743180245,
+          "nanos": 651338000
+        },
+        "done_at": {
+          "seconds": 1743180510,
+          "nanos": 558706000
+        },
+        "ppl_id": "7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
+        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+        "branch_name": "feature/login"
+      },
+      {
+        "name": "build",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1742826854,
+          "nanos": 852498000
+        },
+        "done_at": {
+          "seconds": 1742826923,
+          "nanos": 771318000
+        },
+        "ppl_id": "87887fa3-ced5-4b9b-aa3c-74e65003e55a",
+        "wf_id": "94505eb4-27d2-4d5c-a616-27077ae9ac32",
+        "branch_name": "main"
+      }
+    ])
+}
+
+#[allow(dead_code)]
+pub fn failed_pipeline_details_response_body() -> Value {
+    json!({
+      "pipeline": {
+        "name": "deploy",
+        "state": "DONE",
+        "result": "FAILED",
+        "result_reason": "TEST",
+        "ppl_id": "7ba0d874-33f0-4495-af7c-8cbccb7f56e5",
+        "wf_id": "eb86a134-3081-406a-8ca1-d6e376cf9a65",
+        "branch_name": "main",
+        "commit_sha": "9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d",
+        "commit_author": "alice",
+        "commit_message": "Deploy to the new cluster"
+      },
+      "blocks": [
+        {
+          "name": "Build image",
+          "state": "DONE",
+          "result": "PASSED",
+          "jobs": [
+            {
+              "name": "docker build",
+              "status": "FINISHED",
+              "result": "PASSED"
+            }
+          ]
+        },
+        {
+          "name": "Smoke tests",
+          "state": "DONE",
+          "result": "FAILED",
+          "result_reason": "TEST",
+          "jobs": [
+            {
+              "name": "api",
+              "status": "FINISHED",
+              "result": "PASSED"
+            },
+            {
+              "name": "web",
+              "status": "FINISHED",
+              "result": "FAILED"
+            }
+          ]
+        }
+      ]
+    })
+}
+
+#[allow(dead_code)]
+pub fn running_pipeline_details_response_body() -> Value {
+    json!({
+      "pipeline": {
+        "name": "build",
+        "state": "RUNNING",
+        "ppl_id": "0a3e10c1-f046-4959-ae9d-2677a997a72c",
+        "wf_id": "94505eb4-27d2-4d5c-a616-27077ae9ac32",
+        "branch_name": "main"
+      },
+      "blocks": [
+        {
+          "name": "Compile",
+          "state": "RUNNING",
+          "jobs": [
+            {
+              "name": "cargo build",
+              "status": "RUNNING"
+            }
+          ]
+        }
+      ]
+    })
+}
+
+#[allow(dead_code)]
+pub fn promoted_pipelines_response_body() -> Value {
+    json!([
+      {
+        "name": "build",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1743180000,
+          "nanos": 0
+        },
+        "done_at": {
+          "seconds": 1743180100,
+          "nanos": 0
+        },
+        "ppl_id": "5e0f3c2a-7d41-4b8e-9a62-1c9d8e7f6a50",
+        "wf_id": "c3b2a190-8f7e-4d6c-b5a4-93827161f5e4",
+        "branch_name": "main",
+        "promotion_of": ""
+      },
+      {
+        "name": "deploy-staging",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1743100200,
+          "nanos": 0
+        },
+        "done_at": {
+          "seconds": 1743100300,
+          "nanos": 0
+        },
+        "ppl_id": "9a8b7c6d-5e4f-4a3b-8c2d-1e0f9a8b7c6d",
+        "wf_id": "2f1e0d9c-8b7a-4c6d-9e5f-4a3b2c1d0e9f",
+        "branch_name": "main",
+        "promotion_of": "e4d3c2b1-a0f9-4e8d-b7c6-a5b4c3d2e1f0"
+      },
+      {
+        "name": "build",
+        "state": "DONE",
+        "result": "PASSED",
+        "created_at": {
+          "seconds": 1743100000,
+          "nanos": 0
+        },
+        "done_at": {
+          "seconds": 1743100100,
+          "nanos": 0
+        },
+        "ppl_id": "e4d3c2b1-a0f9-4e8d-b7c6-a5b4c3d2e1f0",
+        "wf_id": "2f1e0d9c-8b7a-4c6d-9e5f-4a3b2c1d0e9f",
+        "branch_name": "main",
+        "promotion_of": ""
       }
     ])
 }
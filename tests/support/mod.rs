@@ -1,2 +1,3 @@
+pub mod feed;
 pub mod fixtures;
-pub mod start_app;
\ No newline at end of file
+pub mod start_app;
@@ -1,15 +1,25 @@
-use actix_web::{App, HttpServer};
-use semaphoreci_cctray::configure_app;
+use actix_web::{web, App, HttpServer};
+use semaphoreci_cctray::config::Config;
+use semaphoreci_cctray::{configure_app, spawn_background_tasks, AppState};
 use std::net::{SocketAddr, TcpListener};
 
-pub async fn start_app(ci_base_uri: &String) -> SocketAddr {
+#[allow(dead_code)]
+pub async fn start_app(ci_base_uri: &str) -> SocketAddr {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(ci_base_uri.to_string());
+
+    start_app_with_config(config).await
+}
+
+pub async fn start_app_with_config(config: Config) -> SocketAddr {
     // Bind to a random free port
     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
     let addr = listener.local_addr().unwrap();
-    let op = Some(ci_base_uri.clone());
+    let state = web::Data::new(AppState::new(&config));
+    spawn_background_tasks(&state);
 
     let server = HttpServer::new(move || {
-        App::new().configure(|cfg| configure_app(cfg, &op))
+        App::new().configure(|cfg| configure_app(cfg, &state))
     })
         .listen(listener)
         .unwrap()
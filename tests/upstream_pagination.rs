@@ -0,0 +1,144 @@
+mod support;
+
+use reqwest::header::{AUTHORIZATION, LINK};
+use semaphoreci_cctray::config::Config;
+use serde_json::json;
+use wiremock::matchers::{header, method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::{get_cctray, get_project_names};
+use support::fixtures;
+use support::start_app::{start_app, start_app_with_config};
+
+async fn mount_paginated_projects(mock_upstream: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(query_param_is_missing("page"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    LINK,
+                    format!(
+                        "<{}/api/v1alpha/projects?page=1>; rel=\"first\", <{}/api/v1alpha/projects?page=2>; rel=\"next\"",
+                        mock_upstream.uri(),
+                        mock_upstream.uri()
+                    )
+                    .as_str(),
+                )
+                .set_body_json(json!([{ "metadata": { "name": "first-project", "id": "first-project-id" } }])),
+        )
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(query_param("page", "2"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    LINK,
+                    format!("<{}/api/v1alpha/projects?page=1>; rel=\"first\"", mock_upstream.uri()).as_str(),
+                )
+                .set_body_json(fixtures::projects_response_body()),
+        )
+        .mount(mock_upstream)
+        .await;
+}
+
+#[actix_web::test]
+async fn finds_project_listed_on_a_later_page() {
+    let mock_upstream = MockServer::start().await;
+    mount_paginated_projects(&mock_upstream).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    let addr = start_app(&mock_upstream.uri()).await;
+
+    let res = get_cctray(addr, "/any-org/my-project/cctray").await;
+
+    assert_eq!(res.status(), 200);
+}
+
+async fn mount_paginated_pipelines(mock_upstream: &MockServer, second_page_requests: u64) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(query_param_is_missing("page"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    LINK,
+                    "</api/v1alpha/pipelines?project_id=my-project-id&page=2>; rel=\"next\"",
+                )
+                .set_body_json(fixtures::pipelines_response_body()),
+        )
+        .mount(mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .and(query_param("page", "2"))
+        .and(header(AUTHORIZATION, "Token : my-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::older_pipelines_response_body()))
+        .expect(second_page_requests)
+        .mount(mock_upstream)
+        .await;
+}
+
+#[actix_web::test]
+async fn merges_pipelines_from_all_pages() {
+    let mock_upstream = MockServer::start().await;
+    mount_paginated_pipelines(&mock_upstream, 1).await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.upstream.max_pipeline_pages = 2;
+    let addr = start_app_with_config(config).await;
+
+    let names = get_project_names(addr, "/any-org/my-project/cctray").await;
+
+    assert!(names.contains(&String::from("lint")), "{:?}", names);
+}
+
+#[actix_web::test]
+async fn reads_a_single_page_of_pipelines_by_default() {
+    let mock_upstream = MockServer::start().await;
+    mount_paginated_pipelines(&mock_upstream, 0).await;
+
+    let addr = start_app(&mock_upstream.uri()).await;
+
+    let names = get_project_names(addr, "/any-org/my-project/cctray").await;
+
+    assert!(!names.contains(&String::from("lint")), "{:?}", names);
+}
+
+#[actix_web::test]
+async fn stops_following_pages_at_the_configured_limit() {
+    let mock_upstream = MockServer::start().await;
+    mount_paginated_projects(&mock_upstream).await;
+
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.upstream.max_pages = 1;
+    let addr = start_app_with_config(config).await;
+
+    let res = get_cctray(addr, "/any-org/my-project/cctray").await;
+
+    assert_eq!(res.status(), 404);
+}
@@ -0,0 +1,190 @@
+mod support;
+
+use reqwest::header::WARNING;
+use semaphoreci_cctray::config::Config;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use support::feed::get_cctray;
+use support::fixtures;
+use support::start_app::start_app_with_config;
+
+fn config(mock_upstream: &MockServer) -> Config {
+    let mut config = Config::default();
+    config.upstream.base_url = Some(mock_upstream.uri());
+    config.upstream.retry_backoff = Duration::from_millis(1);
+    config
+}
+
+const FEED: &str = "/any-org/my-project/cctray";
+
+async fn mount_pipelines(mock_upstream: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/pipelines"))
+        .and(query_param("project_id", "my-project-id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::pipelines_response_body()))
+        .mount(mock_upstream)
+        .await;
+}
+
+#[actix_web::test]
+async fn retries_server_errors() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    mount_pipelines(&mock_upstream).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn gives_up_after_max_retries() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&mock_upstream)
+        .await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 502);
+}
+
+#[actix_web::test]
+async fn does_not_retry_client_errors() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 401);
+}
+
+#[actix_web::test]
+async fn waits_for_retry_after_on_too_many_requests() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .mount(&mock_upstream)
+        .await;
+
+    mount_pipelines(&mock_upstream).await;
+
+    let addr = start_app_with_config(config(&mock_upstream)).await;
+
+    let started_at = Instant::now();
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+    assert!(started_at.elapsed() >= Duration::from_secs(1));
+}
+
+#[actix_web::test]
+async fn times_out_hung_requests() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = config(&mock_upstream);
+    config.upstream.read_timeout = Duration::from_millis(200);
+    let addr = start_app_with_config(config).await;
+
+    let started_at = Instant::now();
+    assert_eq!(get_cctray(addr, FEED).await.status(), 502);
+    assert!(started_at.elapsed() < Duration::from_secs(5));
+}
+
+#[actix_web::test]
+async fn serves_last_good_feed_while_circuit_is_open() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixtures::projects_response_body()))
+        .up_to_n_times(1)
+        .mount(&mock_upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    mount_pipelines(&mock_upstream).await;
+
+    let mut config = config(&mock_upstream);
+    config.upstream.max_retries = 0;
+    config.upstream.circuit_breaker_threshold = 1;
+    let addr = start_app_with_config(config).await;
+
+    let good = get_cctray(addr, FEED).await;
+    assert_eq!(good.status(), 200);
+    assert!(good.headers().get(WARNING).is_none());
+    let good_body = good.text().await.unwrap();
+
+    // Opens the circuit, the last good feed being served meanwhile
+    assert_eq!(get_cctray(addr, FEED).await.status(), 200);
+
+    let stale = get_cctray(addr, FEED).await;
+    assert_eq!(stale.status(), 200);
+    assert_eq!(stale.headers().get(WARNING).unwrap(), "110 - \"Response is Stale\"");
+    assert_eq!(stale.text().await.unwrap(), good_body);
+}
+
+#[actix_web::test]
+async fn fails_fast_while_circuit_is_open_without_a_good_feed() {
+    let mock_upstream = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v1alpha/projects"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&mock_upstream)
+        .await;
+
+    let mut config = config(&mock_upstream);
+    config.upstream.max_retries = 0;
+    config.upstream.circuit_breaker_threshold = 1;
+    let addr = start_app_with_config(config).await;
+
+    assert_eq!(get_cctray(addr, FEED).await.status(), 502);
+    assert_eq!(get_cctray(addr, FEED).await.status(), 503);
+}